@@ -1,87 +1,328 @@
-use std::collections::HashMap;
-
-use rand::Rng;
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use bitvec::prelude::*;
+use serde::Serialize;
 
 use crate::grid::Grid;
 use crate::ga::GA;
-use crate::constants::{
-    MAX_ALIVE_RATIO, 
-    INITIAL_PROBABILITY, 
-    MAX_POPULATION_AGE, 
-    MAX_POPULATION_REPEATS,
-    MAX_STATE_SPACE_SIZE, 
-    MAX_EPSILON, 
-    MIN_EPSILON, 
-    INCREASE_FACTOR, 
-    DECREASE_FACTOR,
-    TOURNAMENT_WINNERS_PERCENTAGE,
-    SELECTION_PRESSURE,
-    MUTATION_RATE,
-    CROSSOVER_RATE,
-};
+use crate::rule::Rule;
+use crate::boundary::Boundary;
+use crate::hashlife::HashLife;
+use crate::census::Census;
+use crate::stats::GenerationStats;
+use crate::grid::Termination;
+use crate::fitness::{Fitness, Episode};
+use crate::qlearning::QLearner;
+use crate::config::{Config, random_seed};
+use crate::constants::INITIAL_PROBABILITY;
+
+// How many random states get_new_state tries before settling for one already in the state space
+const MAX_NEW_STATE_ATTEMPTS: usize = 1000;
+
+// The simulator used to evaluate states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    // Step a Grid generation by generation, with the configured boundary, up to grid.max_population_age
+    Grid,
+    // Jump ahead on the unbounded plane with HashLife, up to agent.hashlife_horizon
+    HashLife,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEngineError(pub String);
+
+impl fmt::Display for ParseEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown engine \"{}\", expected \"grid\" or \"hashlife\"", self.0)
+    }
+}
+
+impl Error for ParseEngineError {}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Grid => write!(f, "grid"),
+            Engine::HashLife => write!(f, "hashlife"),
+        }
+    }
+}
+
+impl FromStr for Engine {
+    type Err = ParseEngineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "grid" => Ok(Engine::Grid),
+            "hashlife" => Ok(Engine::HashLife),
+            _ => Err(ParseEngineError(s.to_string())),
+        }
+    }
+}
+
+// How the agent searches for seeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Explore random seeds with a probability of epsilon, otherwise evolve the state space with the GA
+    Evolve,
+    // Build a seed by placing cells one at a time, choosing each with the QLearner
+    QLearning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStrategyError(pub String);
+
+impl fmt::Display for ParseStrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown strategy \"{}\", expected \"evolve\" or \"q-learning\"", self.0)
+    }
+}
+
+impl Error for ParseStrategyError {}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Evolve => write!(f, "evolve"),
+            Strategy::QLearning => write!(f, "q-learning"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = ParseStrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "evolve" => Ok(Strategy::Evolve),
+            "q-learning" | "qlearning" => Ok(Strategy::QLearning),
+            _ => Err(ParseStrategyError(s.to_string())),
+        }
+    }
+}
+
+// Whether a round of training searched for new random states or evolved the known ones
+// A round of q-learning explored when any of its placements was a random one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Explore,
+    Exploit,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Explore => write!(f, "explore"),
+            Decision::Exploit => write!(f, "exploit"),
+        }
+    }
+}
+
+// What one round of training did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrainingRound {
+    pub decision: Decision,
+    pub states_evaluated: usize,
+    pub duration: Duration,
+}
 
 pub struct Agent {
-    pub state_space: HashMap<BitVec, f32>,
+    // Ordered, so that going through the states never depends on how they happen to be hashed
+    pub state_space: BTreeMap<BitVec, f32>,
+    // States added to the state space but not evaluated yet, which hold INITIAL_PROBABILITY until they are
+    // Any value, 0 included, can be a real score, so it cannot say whether a state has been evaluated
+    pub pending: BTreeSet<BitVec>,
     pub epsilon: f32,
+    pub columns: usize,
+    pub rows: usize,
     pub num_cells: usize,
     pub previous_avg_value: f32,
     pub max_value: f32,
     pub max_state: BitVec,
     pub ga: GA,
+
+    // Learns to place cells, when the strategy is q-learning
+    pub q_learner: QLearner,
+
+    // What each evaluated state turned into by the end of its run
+    pub censuses: HashMap<BitVec, Census>,
+
+    // The statistics of every generation of each evaluated state, when agent.record_history is set
+    // HashLife jumps over generations, so only states evaluated on the grid have one
+    pub histories: HashMap<BitVec, Vec<GenerationStats>>,
+
+    // The Life-like rule every state is evaluated under
+    pub rule: Rule,
+    pub boundary: Boundary,
+    pub engine: Engine,
+
+    // Scores each state's run
+    pub fitness: Box<dyn Fitness>,
+
+    // The settings the agent was created with
+    pub config: Config,
+
+    // Seed for the agent's random numbers, kept with checkpoints
+    // Every random choice the agent and its GA make is drawn from rng, so a run only depends on the seed and the settings
+    pub seed: u64,
+    pub rng: StdRng,
+
+    // Rounds of training so far, including those before the agent was resumed from a checkpoint
+    pub episodes: usize,
 }
 
 impl Agent {
-    pub fn new(epsilon: f32, num_cells: usize) -> Self {
+    pub fn new(config: &Config, fitness: Box<dyn Fitness>) -> Self {
+        let (columns, rows) = (config.grid.columns, config.grid.rows);
+        let num_cells = columns * rows;
+
+        // Without a seed, draw one, and keep it in the agent's config so the run can be repeated
+        let seed = config.agent.seed.unwrap_or_else(random_seed);
+        let mut config = config.clone();
+        config.agent.seed = Some(seed);
+
         // Initialize the GA
-        let ga = GA::new(TOURNAMENT_WINNERS_PERCENTAGE, SELECTION_PRESSURE, MUTATION_RATE, CROSSOVER_RATE);
+        let ga = GA::new(&config.ga, columns, rows);
+        let q_learner = QLearner::new(&config.qlearning, columns, rows);
 
         Agent { 
-            state_space: HashMap::new(), 
-            epsilon, 
+            state_space: BTreeMap::new(), 
+            pending: BTreeSet::new(),
+            epsilon: config.agent.epsilon, 
+            columns,
+            rows,
             num_cells,
             previous_avg_value: 0.0,
             max_value: 0.0,
             max_state: bitvec![0; num_cells],
             ga,
+            q_learner,
+            censuses: HashMap::new(),
+            histories: HashMap::new(),
+            rule: config.grid.rule,
+            boundary: config.grid.boundary,
+            engine: config.agent.engine,
+            fitness,
+            config,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            episodes: 0,
         }
     }
 
-    pub fn update(&mut self, w: usize, h: usize) {
-        // Clone the keys to avoid borrow conflict
-        let keys: Vec<BitVec> = self.state_space.keys().cloned().collect();
-    
-        // Iterate over the cloned keys
-        for grid_state in keys {
-            // Only update states with a probability of 0
-            if let Some(&probability) = self.state_space.get(&grid_state) {
-                if probability == 0.0 {
-                    let state_probability = self.run_state(w, h, &grid_state);
-                    self.state_space.insert(grid_state, state_probability);
-                }
-            }
-        }
+    // Evaluates every state which has not been yet, returning how many there were
+    pub fn update(&mut self) -> usize {
+        let states_evaluated = self.evaluate();
     
         // Prune the state space if it exceeds the maximum size
-        if self.state_space.len() > MAX_STATE_SPACE_SIZE {
+        if self.state_space.len() > self.config.agent.max_state_space_size {
             self.prune();
         }
+
+        // Pruning can remove the best state, so it is looked for again after
+        self.find_best_state();
     
         // Update epsilon
         self.update_epsilon();
+
+        states_evaluated
+    }
+
+    fn evaluate(&mut self) -> usize {
+        // Only evaluate the states added since the last evaluation
+        let keys: Vec<BitVec> = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter(|state| self.state_space.contains_key(state))
+            .collect();
+
+        let results = self.run_states(&keys);
+        let states_evaluated = keys.len();
+
+        for (grid_state, (state_probability, episode)) in keys.into_iter().zip(results) {
+            self.censuses.insert(grid_state.clone(), episode.census);
+            if let Some(history) = episode.history {
+                self.histories.insert(grid_state.clone(), history);
+            }
+            self.state_space.insert(grid_state, state_probability);
+        }
+
+        states_evaluated
+    }
+
+    // One round of training: add states as the strategy decides, then evaluate them
+    pub fn train(&mut self) -> TrainingRound {
+        let start = Instant::now();
+
+        let (decision, states_evaluated) = match self.config.agent.strategy {
+            Strategy::Evolve => self.train_evolve(),
+            Strategy::QLearning => self.train_q_learning(),
+        };
+        self.episodes += 1;
+
+        TrainingRound { decision, states_evaluated, duration: start.elapsed() }
+    }
+
+    fn train_evolve(&mut self) -> (Decision, usize) {
+        // Explore with a probability of epsilon, or always while there are too few states to evolve
+        let decision = if self.rng.gen::<f32>() < self.epsilon || self.state_space.len() < 5 {
+            self.explore();
+            Decision::Explore
+        } else {
+            self.exploit();
+            Decision::Exploit
+        };
+
+        // With new states having been added to the state space, they need to be evaluated
+        (decision, self.update())
+    }
+
+    // One episode of the placement MDP, with the seed's value as its reward
+    fn train_q_learning(&mut self) -> (Decision, usize) {
+        let episode = self.q_learner.play(self.epsilon, &mut self.rng);
+        self.add_pending(episode.seed.clone());
+
+        // The reward is read before updating, since pruning could remove the seed
+        let states_evaluated = self.evaluate();
+        let reward = self.state_space[&episode.seed];
+        self.q_learner.learn(&episode, reward);
+        self.update();
+
+        let decision = if episode.explored { Decision::Explore } else { Decision::Exploit };
+        (decision, states_evaluated)
+    }
+
+    // Adds a state from outside the agent, e.g. an imported pattern, so it is evaluated on the next update
+    pub fn add_state(&mut self, state: BitVec) {
+        if state.len() == self.num_cells {
+            self.add_pending(state);
+        }
+    }
+
+    // Adds a state to be evaluated on the next update, unless it is already known
+    fn add_pending(&mut self, state: BitVec) {
+        if !self.state_space.contains_key(&state) {
+            self.state_space.insert(state.clone(), INITIAL_PROBABILITY);
+            self.pending.insert(state);
+        }
     }
-    
 
     pub fn explore(&mut self) {
         // Generate a new state and add it to the state space
-        let new_state = self.get_new_state();
-        self.state_space.insert(new_state.clone(), INITIAL_PROBABILITY);
+        self.get_new_state();
     }
 
     pub fn exploit(&mut self) {
         // We will pass state_space over to our GA to evolve it and it will return a vector of new states
         // which we will then have to run and evaluate
-        let new_states = match self.ga.evolve(&self.state_space) {
+        let new_states = match self.ga.evolve(&self.state_space, &mut self.rng) {
             Some(states) => states,
             None => {
                 println!("GA failed to evolve the state space");
@@ -89,116 +330,211 @@ impl Agent {
             }
         };
 
-        // Add each state to the state space with INITIAL_PROBABILITY
+        // Add each state to the state space with INITIAL_PROBABILITY, to be evaluated
         for state in new_states {
-            self.state_space.insert(state, INITIAL_PROBABILITY);
+            self.add_pending(state);
         }
     }
 
-    fn run_state(&mut self, w: usize, h: usize, state: &BitVec) -> f32 {
-        let mut grid = Grid::new(w as f32, h as f32, state);
-        let mut iterations = 0;
-        let mut cycle_average_repeats = 0;
-        let mut last_cycle_avg = grid.cycle_average;
+    // Evaluates the states across the worker pool, returning their probabilities and episodes in the same order
+    fn run_states(&self, states: &[BitVec]) -> Vec<(f32, Episode)> {
+        let workers = match self.config.agent.evaluation_workers {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
 
-        while grid.population > 0 && iterations < MAX_POPULATION_AGE &&  cycle_average_repeats < MAX_POPULATION_REPEATS {
-            grid.update();
-            iterations += 1;
-
-            // Check if the population size has repeated cyclically
-            if grid.cycle_average == last_cycle_avg {
-                cycle_average_repeats += 1;
-            } else {
-                last_cycle_avg = grid.cycle_average;
-                cycle_average_repeats = 0;
-            }
+        if workers <= 1 || states.len() <= 1 {
+            return states.iter().map(|state| self.run_state(state)).collect();
         }
 
-        // Evaluate the state based on the final population size
-        let population_difference = (grid.final_population as f32 - grid.initial_population as f32) / self.num_cells as f32;
+        // Every state is evaluated independently, so give each worker a contiguous chunk
+        let chunk_size = states.len().div_ceil(workers);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = states
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|state| self.run_state(state)).collect::<Vec<(f32, Episode)>>()))
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("State evaluation worker panicked"))
+                .collect()
+        })
+    }
+
+    fn run_state(&self, state: &BitVec) -> (f32, Episode) {
+        // HashLife cannot run rules with B0, so those fall back to the grid
+        let episode = match self.engine {
+            Engine::HashLife => self.run_hashlife(state),
+            Engine::Grid => None,
+        };
+
+        let episode = match episode {
+            Some(episode) => episode,
+            None => self.run_grid(state),
+        };
+
+        (self.fitness.score(&episode), episode)
+    }
+
+    fn run_grid(&self, state: &BitVec) -> Episode {
+        let mut grid = Grid::new(&self.config.grid, state);
+        let initial_bounding_box = grid.bits.bounding_box();
+        if self.config.agent.record_history {
+            grid.record_history();
+        }
 
-        // Get the grid's population age and normalize it with MAX_POPULATION_AGE
-        let population_age = grid.population_age as f32 / MAX_POPULATION_AGE as f32;
+        while !grid.is_finished() {
+            grid.update();
+        }
 
-        // Calculate a scaled difference using an exponential function
-        // This will ensure that positive differences are amplified and negative differences are diminished
-        let scaled_difference = 1.0 / (1.0 + f32::exp(-population_difference));
+        Episode::from_grid(grid, initial_bounding_box)
+    }
 
-        // Including the standard deviation in the state probability calculation will encourage the agent to explore
-        // states which have more dynamic populatation fluctuations
-        let standard_deviation = grid.standard_deviation / self.num_cells as f32;
+    // Same as run_grid, but runs the state on the unbounded plane for up to agent.hashlife_horizon generations
+    fn run_hashlife(&self, state: &BitVec) -> Option<Episode> {
+        let horizon = self.config.agent.hashlife_horizon;
+        let num_samples = self.config.agent.hashlife_samples;
+        let max_period = self.config.grid.max_cycle_length;
+        let mut life = HashLife::new(self.columns, self.rows, state, self.rule)?;
+        let initial_population = life.population() as usize;
+        let initial_bounding_box = life.bounding_box();
+
+        // The run ends once the population has died out or settled into a cycle, and stays ended from then on
+        let has_ended = |life: &mut HashLife, generation: u64| {
+            life.seek(generation);
+            life.population() == 0 || life.period(max_period).is_some()
+        };
 
-        // Set the state's probability based on the population difference and population age
-        let mut state_probability = scaled_difference * population_age * standard_deviation;
+        // Jump ahead by doubling the generation until the run has ended, then binary search for exactly when it did
+        let mut population_age = 0;
+        if !has_ended(&mut life, 0) {
+            let mut lower = 0;
+            let mut upper = 1;
+            while upper < horizon && !has_ended(&mut life, upper) {
+                lower = upper;
+                upper = (upper * 2).min(horizon);
+            }
 
-        // Clamp the state probability between 0.0 and 1.0
-        state_probability = state_probability.max(0.0).min(1.0);
+            if has_ended(&mut life, upper) {
+                while upper - lower > 1 {
+                    let middle = lower + (upper - lower) / 2;
+                    if has_ended(&mut life, middle) {
+                        upper = middle;
+                    } else {
+                        lower = middle;
+                    }
+                }
+            }
 
-        // Update the max value if the state probability is greater than the current max value
-        if state_probability > self.max_value {
-            self.max_value = state_probability;
+            population_age = upper;
         }
-        
-        state_probability
+
+        // Sample the population evenly over the run for the standard deviation and the peak
+        let samples: Vec<f32> = (1..=num_samples)
+            .map(|i| life.population_at(population_age * i / num_samples) as f32)
+            .collect();
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance = samples.iter().map(|p| (p - mean).powi(2)).sum::<f32>() / (samples.len() - 1).max(1) as f32;
+        let peak_population = samples.iter().fold(initial_population as f32, |peak, &p| peak.max(p)) as usize;
+
+        life.seek(population_age);
+        let termination = match life.period(max_period) {
+            _ if life.population() == 0 => Termination::Extinct,
+            Some(period) => Termination::Cycle { period },
+            None => Termination::MaxAge,
+        };
+
+        Some(Episode {
+            termination,
+            generations: population_age as usize,
+            max_generations: horizon as usize,
+            num_cells: self.num_cells,
+            initial_population,
+            final_population: life.population() as usize,
+            peak_population,
+            standard_deviation: variance.sqrt(),
+            initial_bounding_box,
+            final_bounding_box: life.bounding_box(),
+            census: Census::new(&life.live_cells(), self.rule, max_period),
+            history: None,
+            grid: None,
+        })
     }
 
     pub fn get_best_state(&mut self) -> BitVec {
         if self.state_space.is_empty() {
             // Generate a new state and add it to the state space
-            let new_state = self.get_new_state();
-            self.state_space.insert(new_state.clone(), INITIAL_PROBABILITY);
-            return new_state;
+            return self.get_new_state();
         }
 
+        self.find_best_state();
+        self.max_state.clone()
+    }
+
+    // Keeps max_state and max_value on the highest valued state, which new states take their alive ratio from
+    fn find_best_state(&mut self) {
         let mut best_state = None;
         let mut highest_probability = f32::MIN;
 
         for (state, probability) in &self.state_space {
             if *probability > highest_probability {
                 highest_probability = *probability;
-                best_state = Some(state.clone());
+                best_state = Some(state);
             }
         }
 
-        self.max_value = highest_probability;
-        self.max_state = best_state.clone().unwrap();
-
-        best_state.expect("Expected a best state but found none")
+        if let Some(state) = best_state {
+            self.max_value = highest_probability;
+            self.max_state = state.clone();
+        }
     }
     
     pub fn get_new_state(&mut self) -> BitVec {
-        let mut rng = rand::thread_rng();
-
-        let alive_percentage = if rng.gen::<f32>() < 0.3 {
-            rng.gen_range(0.01..=MAX_ALIVE_RATIO)
+        let alive_percentage = if self.rng.gen::<f32>() < 0.3 {
+            self.rng.gen_range(0.01..=self.config.agent.max_alive_ratio)
         } else {
             self.get_best_state_alive_ratio()
         };
 
         let num_alive_cells = (self.num_cells as f32 * alive_percentage).round() as usize;
+        let mut new_state = self.random_state(num_alive_cells);
 
-        loop {
-            // Initialize all cells to dead
-            let mut new_state = bitvec![0; self.num_cells];
-
-            // Randomly set the specified number of cells to alive
-            let mut alive_cells_set = 0;
-            while alive_cells_set < num_alive_cells {
-                let cell_index = rng.gen_range(0..self.num_cells);
-                if !new_state[cell_index] {
-                    new_state.set(cell_index, true);
-                    alive_cells_set += 1;
-                }
+        let mut attempts = 1;
+        while self.state_space.contains_key(&new_state) {
+            // Nearly every state has been seen, as on a tiny grid, so settle for a known one rather than searching forever
+            if attempts == MAX_NEW_STATE_ATTEMPTS {
+                return new_state;
             }
 
-            // Check if the new state is already in the state space
-            if !self.state_space.contains_key(&new_state) {
-                // Add the new state to the state space with the initial probability
-                self.state_space.insert(new_state.clone(), INITIAL_PROBABILITY);
-                return new_state;
+            // There may be no unseen states with as many cells alive, e.g. when none are, so try a random number of them
+            let alive_percentage = self.rng.gen_range(0.01..=self.config.agent.max_alive_ratio);
+            let num_alive_cells = (self.num_cells as f32 * alive_percentage).round() as usize;
+            new_state = self.random_state(num_alive_cells);
+            attempts += 1;
+        }
+
+        // Add the new state to the state space with the initial probability
+        self.add_pending(new_state.clone());
+        new_state
+    }
+
+    fn random_state(&mut self, num_alive_cells: usize) -> BitVec {
+        // Initialize all cells to dead
+        let mut state = bitvec![0; self.num_cells];
+
+        // Randomly set the specified number of cells to alive
+        let mut alive_cells_set = 0;
+        while alive_cells_set < num_alive_cells {
+            let cell_index = self.rng.gen_range(0..self.num_cells);
+            if !state[cell_index] {
+                state.set(cell_index, true);
+                alive_cells_set += 1;
             }
-            // If the state is already in the state space, loop again to generate a new state
         }
+
+        state
     }
 
     pub fn update_epsilon(&mut self) {
@@ -207,14 +543,14 @@ impl Agent {
 
         if rate_of_change > 0.0 {
             // The average value is increasing: reduce epsilon
-            self.epsilon *= 1.0 - (rate_of_change * DECREASE_FACTOR);
+            self.epsilon *= 1.0 - (rate_of_change * self.config.agent.decrease_factor);
         } else {
             // The average value is stagnant or decreasing: increase epsilon
-            self.epsilon += INCREASE_FACTOR * -rate_of_change; 
+            self.epsilon += self.config.agent.increase_factor * -rate_of_change; 
         }
 
         // Clamp epsilon between a minimum and maximum value
-        self.epsilon = self.epsilon.clamp(MIN_EPSILON, MAX_EPSILON);
+        self.epsilon = self.epsilon.clamp(self.config.agent.min_epsilon, self.config.agent.max_epsilon);
 
         // Update previous average value
         self.previous_avg_value = current_avg_value;
@@ -222,16 +558,16 @@ impl Agent {
 
     fn get_best_state_alive_ratio(&self) -> f32 {
         if self.state_space.len() < 5 {
-            return MAX_ALIVE_RATIO;
+            return self.config.agent.max_alive_ratio;
         }
 
-        return self.max_state.count_ones() as f32 / self.num_cells as f32;
+        self.max_state.count_ones() as f32 / self.num_cells as f32
     }
 
     fn get_average_state_value(&self) -> f32 {
         let mut total_probability = 0.0;
 
-        for (_, probability) in &self.state_space {
+        for probability in self.state_space.values() {
             // Check if the probability is NaN
             if probability.is_nan() {
                 continue;
@@ -244,7 +580,7 @@ impl Agent {
 
     // Remove the states with the lowest probability from the state space until it is below the maximum size
     fn prune(&mut self) {
-        while self.state_space.len() > MAX_STATE_SPACE_SIZE {
+        while self.state_space.len() > self.config.agent.max_state_space_size {
             let mut lowest_probability = f32::MAX;
             let mut lowest_probability_state = None;
 
@@ -255,7 +591,70 @@ impl Agent {
                 }
             }
 
-            self.state_space.remove(&lowest_probability_state.expect("Expected a lowest probability state but found none"));
+            let lowest_probability_state = lowest_probability_state.expect("Expected a lowest probability state but found none");
+            self.censuses.remove(&lowest_probability_state);
+            self.histories.remove(&lowest_probability_state);
+            self.pending.remove(&lowest_probability_state);
+            self.state_space.remove(&lowest_probability_state);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(columns: usize, rows: usize, seed: u64) -> Agent {
+        let mut config = Config::default();
+        config.grid.columns = columns;
+        config.grid.rows = rows;
+        config.grid.max_population_age = 100;
+        config.agent.seed = Some(seed);
+        Agent::new(&config, Box::new(config.agent.fitness))
+    }
+
+    #[test]
+    fn training_keeps_track_of_the_best_state() {
+        // Without keeping the best state up to date, new states took the alive ratio of the empty state, so the
+        // empty seed was generated and then looked for forever
+        let mut agent = agent(10, 10, 42);
+        for _ in 0..30 {
+            agent.train();
+
+            let best = agent.state_space.values().copied().fold(f32::MIN, f32::max);
+            assert_eq!(agent.max_value, best);
+            assert_eq!(agent.state_space.get(&agent.max_state), Some(&best));
+        }
+    }
+
+    #[test]
+    fn new_states_are_found_when_few_are_left() {
+        // A 2 by 2 grid only has 15 states with at most three of its cells alive, so the last few take many attempts
+        let mut agent = agent(2, 2, 0);
+        for _ in 0..15 {
+            agent.get_new_state();
         }
+        assert_eq!(agent.state_space.len(), 15);
+
+        // With none left, a known state is given back instead of searching forever
+        let state = agent.get_new_state();
+        assert!(agent.state_space.contains_key(&state));
+    }
+
+    #[test]
+    fn states_scoring_zero_are_evaluated_once() {
+        // A lone cell dies straight away, so its final population, and value, is 0
+        let mut agent = agent(10, 10, 5);
+        agent.fitness = Box::new(crate::fitness::BuiltinFitness::FinalPopulation);
+        let mut state = bitvec![0; 100];
+        state.set(55, true);
+
+        agent.add_state(state.clone());
+        assert_eq!(agent.update(), 1);
+        assert_eq!(agent.state_space.get(&state), Some(&0.0));
+
+        // Neither another update nor adding it again evaluates it a second time
+        assert_eq!(agent.update(), 0);
+        agent.add_state(state);
+        assert_eq!(agent.update(), 0);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+use bitvec::prelude::*;
+
+use game_of_life::agent::Agent;
+use game_of_life::pattern::{self, Pattern};
+use game_of_life::config::Config;
+use game_of_life::checkpoint::Checkpoint;
+use game_of_life::metrics::MetricsLog;
+use game_of_life::constants::{CONFIG_PATH, METRICS_PATH};
+
+const USAGE: &str = "Usage: train [OPTIONS] [PATTERN]...
+
+Trains the agent without opening a window, recording every episode to the output directory, then writes the best seeds there.
+Patterns (.rle, .cells, .lif) are added to the state space before training starts.
+Settings are read from config.toml when it exists, and the options below take precedence over them.
+
+Options:
+    --config PATH     Read the settings from this file instead
+    --set KEY=VALUE   Override a setting, e.g. --set ga.mutation_rate=0.3, may be given more than once
+    --episodes N      Number of rounds of training to run
+    --seconds N       Stop after this many seconds instead, or whichever comes first with --episodes
+    --output DIR      Where to write the results
+    --best N          Number of best seeds to write
+    --resume PATH     Carry on training from a checkpoint
+    --help            Show this message";
+
+struct Options {
+    config: Option<PathBuf>,
+    overrides: Vec<String>,
+    episodes: Option<usize>,
+    time_budget: Option<Duration>,
+    output: Option<PathBuf>,
+    best_seeds: Option<usize>,
+    resume: Option<PathBuf>,
+    patterns: Vec<String>,
+}
+
+fn main() {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let config_path = options.config.clone().or_else(|| Path::new(CONFIG_PATH).exists().then(|| PathBuf::from(CONFIG_PATH)));
+    let config = match Config::load(config_path.as_deref(), &options.overrides) {
+        Ok(config) => config,
+        Err(e) => fail(&format!("Invalid config: {}", e)),
+    };
+
+    let mut agent = match &options.resume {
+        Some(path) => match Checkpoint::load(path).and_then(|checkpoint| checkpoint.to_agent(&config, Box::new(config.agent.fitness))) {
+            Ok(agent) => agent,
+            Err(e) => fail(&format!("Could not resume from {}: {}", path.display(), e)),
+        },
+        None => Agent::new(&config, Box::new(config.agent.fitness)),
+    };
+
+    pattern::import_into(&mut agent, &options.patterns);
+
+    let output = options.output.clone().unwrap_or_else(|| PathBuf::from(&config.training.output));
+    if let Err(e) = fs::create_dir_all(&output) {
+        fail(&format!("Could not create {}: {}", output.display(), e));
+    }
+
+    // A resumed agent keeps the grid and GA of its checkpoint, so those are what its config holds
+    match agent.config.to_toml() {
+        Ok(text) => println!("Training with the config:\n{}", text),
+        Err(e) => eprintln!("Could not write out the config: {}", e),
+    }
+    let path = output.join("config.toml");
+    warn_on_error(&path, agent.config.save(&path).map_err(|e| e.to_string()));
+
+    // Without either limit, train for the configured number of episodes
+    let episodes = match (options.episodes, options.time_budget) {
+        (None, None) => Some(config.training.episodes),
+        (episodes, _) => episodes,
+    };
+
+    // The metrics file keeps the name and format of the configured one, but goes in the output directory
+    let metrics_path = output.join(Path::new(&config.files.metrics).file_name().unwrap_or(METRICS_PATH.as_ref()));
+    // A resumed run carries on the log of the run it was saved from, instead of starting it over
+    let metrics = match &options.resume {
+        Some(_) => MetricsLog::append(&metrics_path, agent.episodes),
+        None => MetricsLog::create(&metrics_path),
+    };
+    let mut metrics = match metrics {
+        Ok(metrics) => metrics,
+        Err(e) => fail(&format!("Could not create {}: {}", metrics_path.display(), e)),
+    };
+
+    let start = Instant::now();
+    let mut episode = 0;
+
+    while episodes.is_none_or(|episodes| episode < episodes) && options.time_budget.is_none_or(|budget| start.elapsed() < budget) {
+        let round = agent.train();
+        episode += 1;
+
+        if let Err(e) = metrics.record(&agent, &round) {
+            fail(&format!("Could not write to {}: {}", metrics_path.display(), e));
+        }
+
+        if episode % 100 == 0 {
+            println!("Episode {}: best value {}, {} states", episode, agent.max_value, agent.state_space.len());
+        }
+    }
+
+    println!("Trained for {} episodes in {:.1}s, best value {}", episode, start.elapsed().as_secs_f64(), agent.max_value);
+
+    write_results(&agent, &output, options.best_seeds.unwrap_or(config.training.best_seeds));
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        config: None,
+        overrides: Vec::new(),
+        episodes: None,
+        time_budget: None,
+        output: None,
+        best_seeds: None,
+        resume: None,
+        patterns: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+
+        match arg.as_str() {
+            "--config" => options.config = Some(PathBuf::from(value()?)),
+            "--set" => options.overrides.push(value()?),
+            "--episodes" => options.episodes = Some(parse_number(&arg, &value()?)?),
+            "--seconds" => {
+                let value = value()?;
+                let seconds = Duration::try_from_secs_f64(parse_number(&arg, &value)?);
+                options.time_budget = Some(seconds.map_err(|_| format!("Invalid value \"{}\" for {}", value, arg))?);
+            }
+            "--output" => options.output = Some(PathBuf::from(value()?)),
+            "--best" => options.best_seeds = Some(parse_number(&arg, &value()?)?),
+            "--resume" => options.resume = Some(PathBuf::from(value()?)),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ => options.patterns.push(arg),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value \"{}\" for {}", value, option))
+}
+
+// Writes the best seeds as .rle files, and a checkpoint to resume from
+fn write_results(agent: &Agent, output: &Path, best_seeds: usize) {
+    let mut states: Vec<(&BitVec, f32)> = agent.state_space.iter().map(|(state, &value)| (state, value)).collect();
+    states.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    for (rank, (state, value)) in states.into_iter().take(best_seeds).enumerate() {
+        let mut pattern = Pattern::from_state(state, agent.columns, agent.rows);
+        pattern.rule = Some(agent.rule);
+        pattern.comments.push(format!("Seed ranked {} by the agent, with a value of {}", rank + 1, value));
+        if let Some(census) = agent.censuses.get(state) {
+            pattern.comments.push(format!("Ends up as {}", census));
+        }
+
+        let path = output.join(format!("best_{:02}.rle", rank + 1));
+        warn_on_error(&path, pattern::write_file(&path, &pattern).map_err(|e| e.to_string()));
+    }
+
+    let path = output.join("checkpoint.json");
+    warn_on_error(&path, Checkpoint::new(agent).save(&path).map_err(|e| e.to_string()));
+
+    println!("Wrote the results to {}", output.display());
+}
+
+fn warn_on_error(path: &Path, result: Result<(), String>) {
+    if let Err(e) = result {
+        eprintln!("Could not write {}: {}", path.display(), e);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,299 @@
+use bitvec::prelude::*;
+
+use crate::rule::Rule;
+use crate::boundary::{Boundary, Neighbor};
+use crate::hashlife::BoundingBox;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// A packed Life board which advances 64 cells per word operation
+// Each row is padded to a whole number of u64 words, bit x of a row holds the cell in column x
+// and the padding bits past the last column are always kept at zero
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    pub columns: usize,
+    pub rows: usize,
+    words_per_row: usize,
+    bits: BitVec<u64, Lsb0>,
+}
+
+impl BitGrid {
+    pub fn new(columns: usize, rows: usize, grid_state: &BitVec) -> Self {
+        let words_per_row = columns.div_ceil(WORD_BITS).max(1);
+        let mut bits = bitvec![u64, Lsb0; 0; rows * words_per_row * WORD_BITS];
+
+        // The grid state is laid out row by row without any padding
+        for idx in grid_state.iter_ones().filter(|&idx| idx < columns * rows) {
+            let (x, y) = (idx % columns, idx / columns);
+            bits.set(y * words_per_row * WORD_BITS + x, true);
+        }
+
+        BitGrid { columns, rows, words_per_row, bits }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.words_per_row * WORD_BITS + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.bits.set(y * self.words_per_row * WORD_BITS + x, alive);
+    }
+
+    // The board laid out row by row without any padding, the way states are
+    pub fn to_state(&self) -> BitVec {
+        let mut state = bitvec![0; self.columns * self.rows];
+        for (x, y) in self.live_cells() {
+            state.set(y * self.columns + x, true);
+        }
+        state
+    }
+
+    pub fn population(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    pub fn live_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let row_bits = self.words_per_row * WORD_BITS;
+        self.bits.iter_ones().map(move |idx| (idx % row_bits, idx / row_bits))
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        self.live_cells().map(|(x, y)| (x as i64, y as i64)).fold(None, |bbox, (x, y)| {
+            Some(match bbox {
+                Some(BoundingBox { min_x, min_y, max_x, max_y }) => {
+                    BoundingBox { min_x: min_x.min(x), min_y: min_y.min(y), max_x: max_x.max(x), max_y: max_y.max(y) }
+                }
+                None => BoundingBox { min_x: x, min_y: y, max_x: x, max_y: y },
+            })
+        })
+    }
+
+    // The number of cells born and the number which died since the previous board of the same size
+    pub fn changes(&self, previous: &BitGrid) -> (usize, usize) {
+        let current = self.bits.as_raw_slice();
+        let previous = previous.bits.as_raw_slice();
+
+        current.iter().zip(previous).fold((0, 0), |(births, deaths), (&now, &before)| {
+            (births + (now & !before).count_ones() as usize, deaths + (before & !now).count_ones() as usize)
+        })
+    }
+
+    // Advances the board by one generation under the given rule and boundary
+    pub fn step(&mut self, rule: Rule, boundary: Boundary) {
+        let words = self.words_per_row;
+        let cols = self.columns as i32;
+        let last_word_mask = self.last_word_mask();
+
+        // The rows just outside of the grid only depend on the boundary, so build them once per step
+        let above = self.ghost_row(-1, boundary);
+        let below = self.ghost_row(self.rows as i32, boundary);
+
+        // For every neighbour count, whether a dead cell is born and whether a live cell survives
+        let transitions: Vec<(usize, bool, bool)> = (0..=8)
+            .map(|n| (n, rule.next_state(false, n), rule.next_state(true, n)))
+            .filter(|&(_, born, survives)| born || survives)
+            .collect();
+
+        let current = self.bits.as_raw_slice();
+        let mut next = vec![0u64; current.len()];
+
+        let mut west = vec![0u64; words];
+        let mut east = vec![0u64; words];
+
+        // Running 4 bit neighbour count, one bit plane per binary digit
+        let mut counts = [vec![0u64; words], vec![0u64; words], vec![0u64; words], vec![0u64; words]];
+
+        for y in 0..self.rows {
+            let row = |r: i32| -> &[u64] {
+                if r < 0 {
+                    &above
+                } else if r >= self.rows as i32 {
+                    &below
+                } else {
+                    &current[r as usize * words..(r as usize + 1) * words]
+                }
+            };
+
+            for count in counts.iter_mut() {
+                count.fill(0);
+            }
+
+            for dy in -1..=1 {
+                let r = y as i32 + dy;
+                let row_words = row(r);
+
+                // The neighbours just past the left and right edges of this row
+                let ghost_west = self.lookup(boundary.resolve(-1, r, self.columns, self.rows));
+                let ghost_east = self.lookup(boundary.resolve(cols, r, self.columns, self.rows));
+
+                shift_west(row_words, ghost_west, &mut west);
+                shift_east(row_words, ghost_east, self.columns, &mut east);
+
+                add_plane(&mut counts, &west);
+                add_plane(&mut counts, &east);
+                if dy != 0 {
+                    add_plane(&mut counts, row_words);
+                }
+            }
+
+            let alive = row(y as i32);
+            for w in 0..words {
+                let [c0, c1, c2, c3] = [counts[0][w], counts[1][w], counts[2][w], counts[3][w]];
+                let mut result = 0u64;
+
+                for &(n, born, survives) in &transitions {
+                    let matches = bit_plane_eq(c0, n & 1) & bit_plane_eq(c1, n & 2) & bit_plane_eq(c2, n & 4) & bit_plane_eq(c3, n & 8);
+                    if born {
+                        result |= matches & !alive[w];
+                    }
+                    if survives {
+                        result |= matches & alive[w];
+                    }
+                }
+
+                next[y * words + w] = result;
+            }
+
+            // Keep the padding past the last column cleared, e.g. for rules with B0
+            next[y * words + words - 1] &= last_word_mask;
+        }
+
+        self.bits.as_raw_mut_slice().copy_from_slice(&next);
+    }
+
+    fn lookup(&self, neighbor: Neighbor) -> bool {
+        match neighbor {
+            Neighbor::Cell(idx) => self.get(idx % self.columns, idx / self.columns),
+            Neighbor::Alive => true,
+            Neighbor::Dead => false,
+        }
+    }
+
+    // Builds the packed row at y = -1 or y = rows as seen through the boundary
+    fn ghost_row(&self, y: i32, boundary: Boundary) -> Vec<u64> {
+        let mut row = vec![0u64; self.words_per_row];
+
+        for x in 0..self.columns {
+            if self.lookup(boundary.resolve(x as i32, y, self.columns, self.rows)) {
+                row[x / WORD_BITS] |= 1 << (x % WORD_BITS);
+            }
+        }
+
+        row
+    }
+
+    fn last_word_mask(&self) -> u64 {
+        match self.columns % WORD_BITS {
+            0 => u64::MAX,
+            used => (1u64 << used) - 1,
+        }
+    }
+}
+
+// Writes the west neighbour of every cell into `out`, i.e. out[x] = row[x - 1]
+fn shift_west(row: &[u64], ghost: bool, out: &mut [u64]) {
+    let mut carry = ghost as u64;
+    for (o, &word) in out.iter_mut().zip(row) {
+        *o = (word << 1) | carry;
+        carry = word >> (WORD_BITS - 1);
+    }
+}
+
+// Writes the east neighbour of every cell into `out`, i.e. out[x] = row[x + 1]
+fn shift_east(row: &[u64], ghost: bool, columns: usize, out: &mut [u64]) {
+    for w in 0..row.len() {
+        let carry = row.get(w + 1).map_or(0, |&next| next << (WORD_BITS - 1));
+        out[w] = (row[w] >> 1) | carry;
+    }
+
+    if ghost {
+        let last = columns - 1;
+        out[last / WORD_BITS] |= 1 << (last % WORD_BITS);
+    }
+}
+
+// Adds a plane of single bit values into the bit sliced counter with a ripple of half adders
+fn add_plane(counts: &mut [Vec<u64>; 4], plane: &[u64]) {
+    for (w, &bit) in plane.iter().enumerate() {
+        let mut carry = bit;
+        for count in counts.iter_mut() {
+            let sum = count[w] ^ carry;
+            carry &= count[w];
+            count[w] = sum;
+        }
+    }
+}
+
+// All ones where the bit plane holds the given digit (non-zero meaning one)
+fn bit_plane_eq(plane: u64, digit: usize) -> u64 {
+    if digit != 0 { plane } else { !plane }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    // Steps a state one cell at a time, looking every neighbour up through the boundary
+    fn naive_step(state: &BitVec, columns: usize, rows: usize, rule: Rule, boundary: Boundary) -> BitVec {
+        let mut next = bitvec![0; columns * rows];
+
+        for y in 0..rows {
+            for x in 0..columns {
+                let mut live_neighbors = 0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if (dx, dy) == (0, 0) {
+                            continue;
+                        }
+                        live_neighbors += match boundary.resolve(x as i32 + dx, y as i32 + dy, columns, rows) {
+                            Neighbor::Cell(idx) => state[idx] as usize,
+                            Neighbor::Alive => 1,
+                            Neighbor::Dead => 0,
+                        };
+                    }
+                }
+                next.set(y * columns + x, rule.next_state(state[y * columns + x], live_neighbors));
+            }
+        }
+
+        next
+    }
+
+    #[test]
+    fn step_matches_a_naive_stepper() {
+        let rules: Vec<Rule> = ["B3/S23", "B36/S23", "B0123478/S34678", "B0/S8", "B1/S"].iter().map(|rule| rule.parse().unwrap()).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for &columns in &[1, 2, 63, 64, 65, 130] {
+            for &rows in &[1, 3, 17] {
+                for &rule in &rules {
+                    for boundary in Boundary::ALL {
+                        let mut state: BitVec = (0..columns * rows).map(|_| rng.gen_bool(0.4)).collect();
+                        let mut grid = BitGrid::new(columns, rows, &state);
+
+                        for generation in 0..4 {
+                            state = naive_step(&state, columns, rows, rule, boundary);
+                            grid.step(rule, boundary);
+                            assert_eq!(
+                                grid.to_state(), state,
+                                "{}x{} under {} with a {} boundary, generation {}", columns, rows, rule, boundary.name(), generation + 1
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn step_keeps_the_padding_clear() {
+        let rule: Rule = "B0/S".parse().unwrap();
+        let mut grid = BitGrid::new(65, 2, &bitvec![0; 130]);
+
+        grid.step(rule, Boundary::Dead);
+        assert_eq!(grid.population(), 130);
+        assert!(grid.live_cells().all(|(x, y)| x < 65 && y < 2));
+    }
+}
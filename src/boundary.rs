@@ -0,0 +1,184 @@
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+
+// How neighbours outside of the grid are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Boundary {
+    // Everything outside the grid is dead
+    #[default]
+    Dead,
+    // Everything outside the grid is alive
+    Alive,
+    // Both edges wrap around
+    Torus,
+    // Left and right edges wrap around, top and bottom wrap with the columns mirrored
+    KleinBottle,
+    // Both edges wrap with the other axis mirrored (the real projective plane)
+    CrossSurface,
+    // The edges act as mirrors, so a cell just outside the grid reflects the edge cell
+    Reflective,
+}
+
+// What a neighbour lookup resolves to once the boundary has been applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbor {
+    Cell(usize),
+    Alive,
+    Dead,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 6] = [
+        Boundary::Dead,
+        Boundary::Alive,
+        Boundary::Torus,
+        Boundary::KleinBottle,
+        Boundary::CrossSurface,
+        Boundary::Reflective,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Dead => "dead",
+            Boundary::Alive => "alive",
+            Boundary::Torus => "torus",
+            Boundary::KleinBottle => "klein",
+            Boundary::CrossSurface => "cross",
+            Boundary::Reflective => "reflect",
+        }
+    }
+
+    // Resolves the coordinates of a neighbour, which may lie at most one cell outside of the grid
+    pub fn resolve(&self, x: i32, y: i32, columns: usize, rows: usize) -> Neighbor {
+        let cols = columns as i32;
+        let rows = rows as i32;
+        let x_outside = x < 0 || x >= cols;
+        let y_outside = y < 0 || y >= rows;
+
+        if !x_outside && !y_outside {
+            return Neighbor::Cell((y * cols + x) as usize);
+        }
+
+        let (x, y) = match self {
+            Boundary::Dead => return Neighbor::Dead,
+            Boundary::Alive => return Neighbor::Alive,
+            Boundary::Torus => (x.rem_euclid(cols), y.rem_euclid(rows)),
+            Boundary::KleinBottle => {
+                let x = x.rem_euclid(cols);
+                if y_outside {
+                    (cols - 1 - x, y.rem_euclid(rows))
+                } else {
+                    (x, y)
+                }
+            }
+            Boundary::CrossSurface => {
+                let (mut x, mut y) = (x, y);
+                if x_outside {
+                    x = x.rem_euclid(cols);
+                    y = rows - 1 - y;
+                }
+                if y_outside {
+                    y = y.rem_euclid(rows);
+                    x = cols - 1 - x;
+                }
+                (x, y)
+            }
+            Boundary::Reflective => (x.clamp(0, cols - 1), y.clamp(0, rows - 1)),
+        };
+
+        Neighbor::Cell((y * cols + x) as usize)
+    }
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBoundaryError(pub String);
+
+impl fmt::Display for ParseBoundaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = Boundary::ALL.iter().map(|b| b.name()).collect();
+        write!(f, "unknown boundary \"{}\", expected one of: {}", self.0, names.join(", "))
+    }
+}
+
+impl Error for ParseBoundaryError {}
+
+impl FromStr for Boundary {
+    type Err = ParseBoundaryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        Boundary::ALL
+            .iter()
+            .copied()
+            .find(|b| b.name() == name)
+            .ok_or_else(|| ParseBoundaryError(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::prelude::*;
+    use crate::bitgrid::BitGrid;
+
+    // Cells of a grid 4 columns wide and 3 rows high
+    fn cell(x: usize, y: usize) -> Neighbor {
+        Neighbor::Cell(y * 4 + x)
+    }
+
+    #[test]
+    fn cells_inside_the_grid_are_themselves() {
+        for boundary in Boundary::ALL {
+            assert_eq!(boundary.resolve(2, 1, 4, 3), cell(2, 1), "{}", boundary);
+        }
+    }
+
+    #[test]
+    fn edges_and_corners_resolve_by_topology() {
+        // Just past the left, right, top and bottom edges, then past the top left and bottom right corners
+        let outside = [(-1, 0), (4, 0), (1, -1), (1, 3), (-1, -1), (4, 3)];
+        let expected = [
+            (Boundary::Dead, [Neighbor::Dead; 6]),
+            (Boundary::Alive, [Neighbor::Alive; 6]),
+            (Boundary::Torus, [cell(3, 0), cell(0, 0), cell(1, 2), cell(1, 0), cell(3, 2), cell(0, 0)]),
+            (Boundary::KleinBottle, [cell(3, 0), cell(0, 0), cell(2, 2), cell(2, 0), cell(0, 2), cell(3, 0)]),
+            (Boundary::CrossSurface, [cell(3, 2), cell(0, 2), cell(2, 2), cell(2, 0), cell(0, 0), cell(3, 2)]),
+            (Boundary::Reflective, [cell(0, 0), cell(3, 0), cell(1, 0), cell(1, 2), cell(0, 0), cell(3, 2)]),
+        ];
+
+        for (boundary, neighbors) in expected {
+            for (&(x, y), neighbor) in outside.iter().zip(neighbors) {
+                assert_eq!(boundary.resolve(x, y, 4, 3), neighbor, "{} at ({}, {})", boundary, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn gliders_come_back_around_a_torus() {
+        // A glider moves one cell diagonally every 4 generations, so crosses an 8 by 8 torus in 32
+        let mut state = bitvec![0; 64];
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            state.set(y * 8 + x, true);
+        }
+        let rule = "B3/S23".parse().unwrap();
+
+        let mut torus = BitGrid::new(8, 8, &state);
+        let mut dead = BitGrid::new(8, 8, &state);
+        for generation in 1..=32 {
+            torus.step(rule, Boundary::Torus);
+            dead.step(rule, Boundary::Dead);
+            assert_eq!(torus.population(), 5, "generation {}", generation);
+            assert_eq!(torus.to_state() == state, generation == 32, "generation {}", generation);
+        }
+
+        // With nothing beyond the edges, it crashes into the corner instead
+        assert_ne!(dead.to_state(), state);
+    }
+}
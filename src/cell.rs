@@ -1,8 +0,0 @@
-use nannou::prelude::*;
-
-
-#[derive(Debug, Clone)]
-pub struct Cell {
-    pub pos: Point2,
-    pub state: bool,
-}
\ No newline at end of file
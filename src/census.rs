@@ -0,0 +1,335 @@
+use std::fmt;
+use std::sync::OnceLock;
+use std::collections::{HashMap, HashSet, BTreeMap};
+
+use crate::grid::Grid;
+use crate::rule::Rule;
+use crate::hashlife::BoundingBox;
+
+// A live cell on the unbounded plane, and a set of them moved to the origin and sorted
+type Cell = (i64, i64);
+type Shape = Vec<Cell>;
+
+// Common objects of Conway's Game of Life in one of their phases, as plaintext rows
+const KNOWN_OBJECTS: [(&str, &[&str]); 18] = [
+    ("block", &["OO", "OO"]),
+    ("beehive", &[".OO.", "O..O", ".OO."]),
+    ("loaf", &[".OO.", "O..O", ".O.O", "..O."]),
+    ("boat", &["OO.", "O.O", ".O."]),
+    ("ship", &["OO.", "O.O", ".OO"]),
+    ("tub", &[".O.", "O.O", ".O."]),
+    ("pond", &[".OO.", "O..O", "O..O", ".OO."]),
+    ("barge", &[".O..", "O.O.", ".O.O", "..O."]),
+    ("long boat", &[".O..", "O.O.", ".O.O", "..OO"]),
+    ("snake", &["OO.O", "O.OO"]),
+    ("aircraft carrier", &["OO..", "O..O", "..OO"]),
+    ("blinker", &["OOO"]),
+    ("toad", &[".OOO", "OOO."]),
+    ("beacon", &["OO..", "OO..", "..OO", "..OO"]),
+    ("glider", &[".O.", "..O", "OOO"]),
+    ("lightweight spaceship", &[".O..O", "O....", "O...O", "OOOO."]),
+    ("middleweight spaceship", &["...O..", ".O...O", "O.....", "O....O", "OOOOO."]),
+    ("heavyweight spaceship", &["...OO..", ".O....O", "O......", "O.....O", "OOOOOO."]),
+];
+
+// A group of live cells which evolves on its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    // Only objects of Conway's Game of Life are named
+    pub name: Option<&'static str>,
+    pub population: usize,
+    pub bounding_box: BoundingBox,
+    // Generations until the object repeats, if it does within the longest period looked for, and how far it has moved by then
+    pub period: Option<usize>,
+    pub displacement: (i64, i64),
+}
+
+// What a pattern has turned into, split up into its objects
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Census {
+    pub objects: Vec<Object>,
+}
+
+impl Census {
+    // Every object is run on its own on the unbounded plane to find its period,
+    // so objects which are still interacting with each other or the grid's boundary are not recognised
+    pub fn new(cells: &[Cell], rule: Rule, max_period: usize) -> Self {
+        let mut objects: Vec<Object> = Vec::new();
+
+        // Cells up to two apart belong together, which keeps spaceships in one piece. When that merges objects
+        // which are only close to each other, use the directly connected pieces instead if every one is known
+        for cluster in components(cells, 2) {
+            let object = analyze(&cluster, rule, max_period);
+            if object.name.is_some() {
+                objects.push(object);
+                continue;
+            }
+
+            let pieces: Vec<Object> = components(&cluster, 1).iter().map(|piece| analyze(piece, rule, max_period)).collect();
+            if pieces.len() > 1 && pieces.iter().all(|piece| piece.name.is_some()) {
+                objects.extend(pieces);
+            } else {
+                objects.push(object);
+            }
+        }
+
+        objects.sort_by_key(|object| (object.bounding_box.min_y, object.bounding_box.min_x));
+
+        Census { objects }
+    }
+
+    // Boundaries which wrap around are ignored, so objects crossing an edge are split in two
+    pub fn from_grid(grid: &Grid) -> Self {
+        let cells: Vec<Cell> = (0..grid.rows)
+            .flat_map(|y| (0..grid.columns).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.is_alive(x, y))
+            .map(|(x, y)| (x as i64, y as i64))
+            .collect();
+
+        Census::new(&cells, grid.rule, grid.max_cycle_length)
+    }
+
+    pub fn count(&self, name: &str) -> usize {
+        self.objects.iter().filter(|object| object.name == Some(name)).count()
+    }
+
+    // Objects which move, whether named or not
+    pub fn moving(&self) -> usize {
+        self.objects.iter().filter(|object| object.period.is_some() && object.displacement != (0, 0)).count()
+    }
+}
+
+// Lists the number of each object, e.g. "2 block, 1 blinker, 1 unnamed"
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.objects.is_empty() {
+            return write!(f, "nothing");
+        }
+
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for object in &self.objects {
+            *counts.entry(object.name.unwrap_or("unnamed")).or_insert(0) += 1;
+        }
+
+        let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+        let parts: Vec<String> = counts.iter().map(|(name, count)| format!("{} {}", count, name)).collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+fn analyze(cells: &[Cell], rule: Rule, max_period: usize) -> Object {
+    let (min_x, min_y, shape) = normalize(cells);
+    let mut phases = vec![shape.clone()];
+    let mut current: HashSet<Cell> = cells.iter().copied().collect();
+
+    let mut period = None;
+    let mut displacement = (0, 0);
+
+    for generation in 1..=max_period {
+        current = step(&current, rule);
+        if current.is_empty() {
+            break;
+        }
+
+        let phase: Vec<Cell> = current.iter().copied().collect();
+        let (x, y, phase_shape) = normalize(&phase);
+        if phase_shape == shape {
+            period = Some(generation);
+            displacement = (x - min_x, y - min_y);
+            break;
+        }
+
+        phases.push(phase_shape);
+    }
+
+    // Compare every phase in every orientation against the known objects
+    let name = match period {
+        Some(_) if rule == Rule::conway() => {
+            let phases: Vec<Shape> = phases.iter().map(|phase| canonical(phase)).collect();
+            known_objects().iter().find(|(_, known)| phases.contains(known)).map(|&(name, _)| name)
+        }
+        _ => None,
+    };
+
+    let (max_x, max_y) = shape.iter().fold((0, 0), |(mx, my), &(x, y)| (mx.max(x), my.max(y)));
+
+    Object {
+        name,
+        population: cells.len(),
+        bounding_box: BoundingBox { min_x, min_y, max_x: min_x + max_x, max_y: min_y + max_y },
+        period,
+        displacement,
+    }
+}
+
+// Groups cells which are at most `distance` apart, horizontally, vertically or diagonally
+fn components(cells: &[Cell], distance: i64) -> Vec<Vec<Cell>> {
+    let mut remaining: HashSet<Cell> = cells.iter().copied().collect();
+    let mut groups = Vec::new();
+
+    for &start in cells {
+        if !remaining.remove(&start) {
+            continue;
+        }
+
+        let mut group = vec![start];
+        let mut next = 0;
+        while next < group.len() {
+            let (x, y) = group[next];
+            next += 1;
+
+            for dy in -distance..=distance {
+                for dx in -distance..=distance {
+                    if remaining.remove(&(x + dx, y + dy)) {
+                        group.push((x + dx, y + dy));
+                    }
+                }
+            }
+        }
+
+        groups.push(group);
+    }
+
+    groups
+}
+
+// One generation of the cells on the unbounded plane
+// Cells with no live neighbours are never born here, so rules with B0 are not followed exactly
+fn step(cells: &HashSet<Cell>, rule: Rule) -> HashSet<Cell> {
+    let mut counts: HashMap<Cell, usize> = HashMap::new();
+    for &(x, y) in cells {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) != (0, 0) {
+                    *counts.entry((x + dx, y + dy)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .filter(|&(cell, live_neighbors)| rule.next_state(cells.contains(&cell), live_neighbors))
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+// Moves the cells so their bounding box starts at the origin, returning where it started and the sorted cells
+fn normalize(cells: &[Cell]) -> (i64, i64, Shape) {
+    let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+
+    let mut shape: Shape = cells.iter().map(|&(x, y)| (x - min_x, y - min_y)).collect();
+    shape.sort();
+
+    (min_x, min_y, shape)
+}
+
+// The same shape for every rotation and reflection of it
+fn canonical(shape: &[Cell]) -> Shape {
+    let orientations: [fn(Cell) -> Cell; 8] = [
+        |(x, y)| (x, y),
+        |(x, y)| (-x, y),
+        |(x, y)| (x, -y),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, x),
+        |(x, y)| (-y, x),
+        |(x, y)| (y, -x),
+        |(x, y)| (-y, -x),
+    ];
+
+    orientations
+        .iter()
+        .map(|orient| normalize(&shape.iter().map(|&cell| orient(cell)).collect::<Vec<_>>()).2)
+        .min()
+        .unwrap()
+}
+
+fn known_objects() -> &'static [(&'static str, Shape)] {
+    static KNOWN: OnceLock<Vec<(&'static str, Shape)>> = OnceLock::new();
+
+    KNOWN.get_or_init(|| {
+        KNOWN_OBJECTS
+            .iter()
+            .map(|&(name, rows)| {
+                let cells: Vec<Cell> = rows
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(y, row)| row.chars().enumerate().filter(|&(_, c)| c == 'O').map(move |(x, _)| (x as i64, y as i64)))
+                    .collect();
+                (name, canonical(&cells))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_PERIOD: usize = 24;
+
+    // Live cells of plaintext rows, with 'O' alive, placed with their top left corner at the given position
+    fn cells(rows: &[&str], left: i64, top: i64) -> Vec<Cell> {
+        rows.iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate().filter(|&(_, c)| c == 'O').map(move |(x, _)| (x, y)))
+            .map(|(x, y)| (left + x as i64, top + y as i64))
+            .collect()
+    }
+
+    fn conway() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+
+    #[test]
+    fn common_objects_are_named() {
+        let mut live = cells(&["OO", "OO"], 0, 0);
+        live.extend(cells(&["OOO"], 10, 0));
+        live.extend(cells(&[".O.", "..O", "OOO"], 0, 10));
+        let census = Census::new(&live, conway(), MAX_PERIOD);
+
+        assert_eq!(census.objects.len(), 3);
+        assert_eq!((census.count("block"), census.count("blinker"), census.count("glider")), (1, 1, 1));
+        assert_eq!(census.moving(), 1);
+        assert_eq!(census.to_string(), "1 blinker, 1 block, 1 glider");
+
+        let blinker = census.objects.iter().find(|object| object.name == Some("blinker")).unwrap();
+        assert_eq!((blinker.population, blinker.period, blinker.displacement), (3, Some(2), (0, 0)));
+    }
+
+    #[test]
+    fn gliders_move_one_cell_diagonally_every_four_generations() {
+        let census = Census::new(&cells(&[".O.", "..O", "OOO"], 5, 5), conway(), MAX_PERIOD);
+
+        let glider = &census.objects[0];
+        assert_eq!(glider.name, Some("glider"));
+        assert_eq!((glider.period, glider.displacement), (Some(4), (1, 1)));
+    }
+
+    #[test]
+    fn objects_close_together_are_split_into_known_pieces() {
+        // One column apart, the blocks are a single group of cells up to two apart, which is not a known object
+        let mut live = cells(&["OO", "OO"], 0, 0);
+        live.extend(cells(&["OO", "OO"], 3, 0));
+        let census = Census::new(&live, conway(), MAX_PERIOD);
+
+        assert_eq!(census.objects.len(), 2);
+        assert_eq!(census.count("block"), 2);
+        assert_eq!(census.objects[0].bounding_box.min_x, 0);
+        assert_eq!(census.objects[1].bounding_box.min_x, 3);
+    }
+
+    #[test]
+    fn objects_of_other_rules_are_not_named() {
+        // A block is still life under HighLife too, but the names are only those of Conway's objects
+        let census = Census::new(&cells(&["OO", "OO"], 0, 0), "B36/S23".parse().unwrap(), MAX_PERIOD);
+
+        assert_eq!(census.objects.len(), 1);
+        assert_eq!(census.objects[0].name, None);
+        assert_eq!(census.objects[0].period, Some(1));
+        assert_eq!(census.to_string(), "1 unnamed");
+    }
+}
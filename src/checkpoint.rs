@@ -0,0 +1,286 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::error::Error;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use bitvec::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::agent::Agent;
+use crate::config::{Config, GAConfig};
+use crate::fitness::Fitness;
+
+// Bump whenever the layout of a checkpoint changes, so older checkpoints are refused instead of misread
+pub const CHECKPOINT_VERSION: u32 = 4;
+
+// Everything the agent has learned, along with what is needed to carry on training where it left off
+// States are written as strings of '0' and '1', row by row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub columns: usize,
+    pub rows: usize,
+    pub rule: String,
+    pub boundary: String,
+    pub epsilon: f32,
+    pub previous_avg_value: f32,
+    pub max_value: f32,
+    pub max_state: String,
+    pub seed: u64,
+    // Rounds of training so far, so a resumed run carries on counting them in its metrics
+    pub episodes: usize,
+    pub ga: GAConfig,
+    // The weights the q-learning strategy has learned, one per feature
+    pub q_weights: Vec<f32>,
+    pub state_space: Vec<StateValue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateValue {
+    pub state: String,
+    pub value: f32,
+}
+
+// Only the version is read first, so checkpoints of any other version can be refused whatever their layout
+#[derive(Deserialize)]
+struct Version {
+    version: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    // Checkpoints written before versioning was added have no version, which is reported as 0
+    Version { found: u32, expected: u32 },
+    Invalid(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{}", e),
+            CheckpointError::Format(e) => write!(f, "malformed checkpoint: {}", e),
+            CheckpointError::Version { found, expected } => write!(
+                f,
+                "the checkpoint is version {} but only version {} can be loaded, move it aside to start training from scratch",
+                found, expected
+            ),
+            CheckpointError::Invalid(message) => write!(f, "invalid checkpoint: {}", message),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CheckpointError::Io(e) => Some(e),
+            CheckpointError::Format(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(e: serde_json::Error) -> Self {
+        CheckpointError::Format(e)
+    }
+}
+
+impl Checkpoint {
+    pub fn new(agent: &Agent) -> Self {
+        // The state space is ordered, so the same agent always gives the same file
+        // States valued NaN can never be picked as the best, and JSON has no way to write them, so they are left out
+        // So are states not evaluated yet, which would be taken as having scored INITIAL_PROBABILITY when loaded
+        let state_space: Vec<StateValue> = agent.state_space
+            .iter()
+            .filter(|(state, value)| !value.is_nan() && !agent.pending.contains(*state))
+            .map(|(state, &value)| StateValue { state: state_to_string(state), value })
+            .collect();
+
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            columns: agent.columns,
+            rows: agent.rows,
+            rule: agent.rule.to_string(),
+            boundary: agent.boundary.to_string(),
+            epsilon: agent.epsilon,
+            previous_avg_value: agent.previous_avg_value,
+            max_value: agent.max_value,
+            max_state: state_to_string(&agent.max_state),
+            seed: agent.seed,
+            episodes: agent.episodes,
+            ga: GAConfig {
+                tournament_winners_percentage: agent.ga.tournament_winners_percentage,
+                selection_pressure: agent.ga.selection_pressure,
+                mutation_rate: agent.ga.mutation_rate,
+                crossover_rate: agent.ga.crossover_rate,
+                max_crossover_points: agent.ga.max_crossover_points,
+                max_crossover_section_size: agent.ga.max_crossover_section_size,
+                max_mutation_points: agent.ga.max_mutation_points,
+            },
+            q_weights: agent.q_learner.weights.clone(),
+            state_space,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        let text = fs::read_to_string(path)?;
+
+        let found = serde_json::from_str::<Version>(&text)?.version.unwrap_or(0);
+        if found != CHECKPOINT_VERSION {
+            return Err(CheckpointError::Version { found, expected: CHECKPOINT_VERSION });
+        }
+
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Rebuilds the agent with the given settings, except for the grid, GA and seed the checkpoint was trained with
+    // The random numbers start over from the seed, so resuming the same checkpoint twice gives the same run
+    // The engine and fitness are not part of what the agent has learned, so those come from the settings
+    pub fn to_agent(&self, config: &Config, fitness: Box<dyn Fitness>) -> Result<Agent, CheckpointError> {
+        let mut config = config.clone();
+        config.grid.columns = self.columns;
+        config.grid.rows = self.rows;
+        config.grid.rule = self.rule.parse().map_err(|e| CheckpointError::Invalid(format!("{}", e)))?;
+        config.grid.boundary = self.boundary.parse().map_err(|e| CheckpointError::Invalid(format!("{}", e)))?;
+        config.agent.epsilon = self.epsilon;
+        config.agent.seed = Some(self.seed);
+        config.ga = self.ga.clone();
+        // The checkpoint may have been edited by hand, and settings out of range would only fail once training
+        config.validate().map_err(|e| CheckpointError::Invalid(format!("{}", e)))?;
+        let num_cells = self.columns * self.rows;
+
+        let mut state_space = BTreeMap::new();
+        for StateValue { state, value } in &self.state_space {
+            state_space.insert(self.parse_state(state, num_cells)?, *value);
+        }
+
+        let mut agent = Agent::new(&config, fitness);
+        agent.state_space = state_space;
+        agent.previous_avg_value = self.previous_avg_value;
+        agent.max_value = self.max_value;
+        agent.episodes = self.episodes;
+        agent.max_state = self.parse_state(&self.max_state, num_cells)?;
+
+        if self.q_weights.len() != agent.q_learner.weights.len() {
+            return Err(CheckpointError::Invalid(format!(
+                "there are {} q-learning weights, but the grid needs {}",
+                self.q_weights.len(), agent.q_learner.weights.len()
+            )));
+        }
+        agent.q_learner.weights = self.q_weights.clone();
+
+        Ok(agent)
+    }
+
+    fn parse_state(&self, text: &str, num_cells: usize) -> Result<BitVec, CheckpointError> {
+        if text.len() != num_cells {
+            return Err(CheckpointError::Invalid(format!(
+                "a state has {} cells, but the grid is {}x{}",
+                text.len(), self.columns, self.rows
+            )));
+        }
+
+        text.chars()
+            .map(|c| match c {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                c => Err(CheckpointError::Invalid(format!("unexpected character '{}' in a state", c))),
+            })
+            .collect()
+    }
+}
+
+fn state_to_string(state: &BitVec) -> String {
+    state.iter().map(|cell| if *cell { '1' } else { '0' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitness::BuiltinFitness;
+
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("game_of_life_checkpoint_{}_{}.json", name, std::process::id()))
+    }
+
+    fn trained_agent() -> Agent {
+        let mut config = Config::default();
+        config.grid.columns = 8;
+        config.grid.rows = 6;
+        config.grid.max_population_age = 100;
+        config.agent.seed = Some(11);
+        let mut agent = Agent::new(&config, Box::new(BuiltinFitness::FinalPopulation));
+        for _ in 0..10 {
+            agent.train();
+        }
+        agent
+    }
+
+    #[test]
+    fn saved_checkpoints_load_back_the_same_agent() {
+        let path = path("round_trip");
+        let agent = trained_agent();
+        Checkpoint::new(&agent).save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap().to_agent(&Config::default(), Box::new(BuiltinFitness::FinalPopulation)).unwrap();
+        assert_eq!(loaded.state_space, agent.state_space);
+        assert_eq!((loaded.columns, loaded.rows, loaded.rule, loaded.boundary), (8, 6, agent.rule, agent.boundary));
+        assert_eq!((loaded.seed, loaded.episodes, loaded.epsilon), (agent.seed, 10, agent.epsilon));
+        assert_eq!((&loaded.max_state, loaded.max_value), (&agent.max_state, agent.max_value));
+        assert_eq!(loaded.q_learner.weights, agent.q_learner.weights);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoints_of_other_versions_are_refused() {
+        let path = path("version");
+        let mut checkpoint = serde_json::to_value(Checkpoint::new(&trained_agent())).unwrap();
+
+        checkpoint["version"] = (CHECKPOINT_VERSION - 1).into();
+        fs::write(&path, checkpoint.to_string()).unwrap();
+        let error = Checkpoint::load(&path).unwrap_err();
+        assert!(matches!(error, CheckpointError::Version { found, expected } if found == CHECKPOINT_VERSION - 1 && expected == CHECKPOINT_VERSION));
+
+        // Checkpoints from before versioning have none at all
+        checkpoint.as_object_mut().unwrap().remove("version");
+        fs::write(&path, checkpoint.to_string()).unwrap();
+        let error = Checkpoint::load(&path).unwrap_err();
+        assert!(matches!(error, CheckpointError::Version { found: 0, .. }));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoints_which_do_not_fit_together_are_refused() {
+        let checkpoint = Checkpoint::new(&trained_agent());
+        let to_agent = |checkpoint: &Checkpoint| checkpoint.to_agent(&Config::default(), Box::new(BuiltinFitness::FinalPopulation));
+
+        let mut short_state = checkpoint.clone();
+        short_state.state_space[0].state.pop();
+        assert!(matches!(to_agent(&short_state), Err(CheckpointError::Invalid(_))));
+
+        let mut no_crossover_points = checkpoint.clone();
+        no_crossover_points.ga.max_crossover_points = 0.0;
+        assert!(matches!(to_agent(&no_crossover_points), Err(CheckpointError::Invalid(_))));
+
+        let mut no_columns = checkpoint.clone();
+        no_columns.columns = 0;
+        assert!(matches!(to_agent(&no_columns), Err(CheckpointError::Invalid(_))));
+    }
+}
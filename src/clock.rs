@@ -0,0 +1,89 @@
+use std::fmt;
+use std::time::Duration;
+
+use game_of_life::config::WindowConfig;
+
+// Most generations run in one frame, so a slow frame cannot snowball into ever slower ones
+const MAX_TICKS_PER_FRAME: usize = 1000;
+
+// Decides how many generations the simulation advances by each frame
+// It either runs a number of generations every frame, or runs at a fixed rate whatever the frame rate of the display
+pub struct Clock {
+    pub paused: bool,
+    pub fixed_rate: bool,
+    pub generations_per_frame: usize,
+    pub ticks_per_second: f32,
+
+    // Time owed to the fixed rate which did not add up to a whole generation yet, in generations
+    remainder: f32,
+}
+
+impl Clock {
+    pub fn new(config: &WindowConfig) -> Self {
+        Clock {
+            paused: false,
+            fixed_rate: config.fixed_rate,
+            generations_per_frame: config.generations_per_frame,
+            ticks_per_second: config.ticks_per_second,
+            remainder: 0.0,
+        }
+    }
+
+    // The number of generations to advance by, given the time since the last frame
+    pub fn ticks(&mut self, since_last_frame: Duration) -> usize {
+        if self.paused {
+            return 0;
+        }
+
+        if !self.fixed_rate {
+            return self.generations_per_frame.min(MAX_TICKS_PER_FRAME);
+        }
+
+        self.remainder += since_last_frame.as_secs_f32() * self.ticks_per_second;
+        let ticks = self.remainder.floor();
+        self.remainder -= ticks;
+
+        (ticks as usize).min(MAX_TICKS_PER_FRAME)
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.remainder = 0.0;
+    }
+
+    pub fn toggle_fixed_rate(&mut self) {
+        self.fixed_rate = !self.fixed_rate;
+        self.remainder = 0.0;
+    }
+
+    // Doubles or halves whichever speed is in use
+    pub fn faster(&mut self) {
+        if self.fixed_rate {
+            self.ticks_per_second = (self.ticks_per_second * 2.0).min(1000.0);
+        } else {
+            self.generations_per_frame = (self.generations_per_frame * 2).min(MAX_TICKS_PER_FRAME);
+        }
+    }
+
+    pub fn slower(&mut self) {
+        if self.fixed_rate {
+            self.ticks_per_second = (self.ticks_per_second / 2.0).max(0.25);
+        } else {
+            self.generations_per_frame = (self.generations_per_frame / 2).max(1);
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.paused {
+            write!(f, "paused")
+        } else if self.fixed_rate {
+            write!(f, "{} generations per second", self.ticks_per_second)
+        } else if self.generations_per_frame == 1 {
+            write!(f, "1 generation per frame")
+        } else {
+            write!(f, "{} generations per frame", self.generations_per_frame)
+        }
+    }
+}
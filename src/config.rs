@@ -0,0 +1,474 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::error::Error;
+use std::path::Path;
+
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::rule::Rule;
+use crate::boundary::Boundary;
+use crate::agent::{Engine, Strategy};
+use crate::fitness::BuiltinFitness;
+use crate::pattern::Placement;
+use crate::constants::*;
+
+// TOML integers are signed, so a larger seed could be written to a config file but never read back
+pub const MAX_SEED: u64 = i64::MAX as u64;
+
+// Every setting of a run, read from a TOML file with the values in constants.rs as defaults
+// Each section may be left out of the file, as may any setting within it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub grid: GridConfig,
+    pub agent: AgentConfig,
+    pub ga: GAConfig,
+    pub qlearning: QLearningConfig,
+    pub files: FilesConfig,
+    pub window: WindowConfig,
+    pub training: TrainingConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    pub columns: usize,
+    pub rows: usize,
+    #[serde(with = "display_from_str")]
+    pub rule: Rule,
+    #[serde(with = "display_from_str")]
+    pub boundary: Boundary,
+    pub max_population_age: usize,
+    pub max_cycle_length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    #[serde(with = "display_from_str")]
+    pub strategy: Strategy,
+    #[serde(with = "display_from_str")]
+    pub engine: Engine,
+    pub hashlife_horizon: u64,
+    pub hashlife_samples: u64,
+    #[serde(with = "display_from_str")]
+    pub fitness: BuiltinFitness,
+    pub record_history: bool,
+    pub max_alive_ratio: f32,
+    pub max_state_space_size: usize,
+    pub evaluation_workers: usize,
+    pub seed: Option<u64>,
+    pub epsilon: f32,
+    pub min_epsilon: f32,
+    pub max_epsilon: f32,
+    pub increase_factor: f32,
+    pub decrease_factor: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GAConfig {
+    pub tournament_winners_percentage: f32,
+    pub selection_pressure: f32,
+    pub mutation_rate: f32,
+    pub crossover_rate: f32,
+    pub max_crossover_points: f32,
+    pub max_crossover_section_size: f32,
+    pub max_mutation_points: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QLearningConfig {
+    pub placements: usize,
+    pub learning_rate: f32,
+    pub discount: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilesConfig {
+    pub best_seed: String,
+    pub checkpoint: String,
+    // Where the viewer writes the config it runs with
+    pub effective_config: String,
+    pub metrics: String,
+    #[serde(with = "display_from_str")]
+    pub pattern_placement: Placement,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+    // The viewer runs this many generations every frame, or with fixed_rate, ticks_per_second whatever the frame rate
+    pub generations_per_frame: usize,
+    pub ticks_per_second: f32,
+    pub fixed_rate: bool,
+    // Whether the viewer starts out showing its statistics over the grid
+    pub hud: bool,
+    // Whether the viewer starts out outlining every cell
+    pub grid_lines: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrainingConfig {
+    pub episodes: usize,
+    pub output: String,
+    pub best_seeds: usize,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            columns: GRID_COLUMNS,
+            rows: GRID_ROWS,
+            rule: RULE.parse().expect("Invalid RULE in constants"),
+            boundary: BOUNDARY.parse().expect("Invalid BOUNDARY in constants"),
+            max_population_age: MAX_POPULATION_AGE,
+            max_cycle_length: MAX_CYCLE_LENGTH,
+        }
+    }
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        AgentConfig {
+            strategy: STRATEGY.parse().expect("Invalid STRATEGY in constants"),
+            engine: ENGINE.parse().expect("Invalid ENGINE in constants"),
+            hashlife_horizon: HASHLIFE_HORIZON,
+            hashlife_samples: HASHLIFE_SAMPLES,
+            fitness: FITNESS.parse().expect("Invalid FITNESS in constants"),
+            record_history: RECORD_HISTORY,
+            max_alive_ratio: MAX_ALIVE_RATIO,
+            max_state_space_size: MAX_STATE_SPACE_SIZE,
+            evaluation_workers: EVALUATION_WORKERS,
+            seed: SEED,
+            epsilon: EPSILON,
+            min_epsilon: MIN_EPSILON,
+            max_epsilon: MAX_EPSILON,
+            increase_factor: INCREASE_FACTOR,
+            decrease_factor: DECREASE_FACTOR,
+        }
+    }
+}
+
+impl Default for GAConfig {
+    fn default() -> Self {
+        GAConfig {
+            tournament_winners_percentage: TOURNAMENT_WINNERS_PERCENTAGE,
+            selection_pressure: SELECTION_PRESSURE,
+            mutation_rate: MUTATION_RATE,
+            crossover_rate: CROSSOVER_RATE,
+            max_crossover_points: MAX_CROSSOVER_POINTS,
+            max_crossover_section_size: MAX_CROSSOVER_SECTION_SIZE,
+            max_mutation_points: MAX_MUTATION_POINTS,
+        }
+    }
+}
+
+impl Default for QLearningConfig {
+    fn default() -> Self {
+        QLearningConfig {
+            placements: QLEARNING_PLACEMENTS,
+            learning_rate: QLEARNING_LEARNING_RATE,
+            discount: QLEARNING_DISCOUNT,
+        }
+    }
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        FilesConfig {
+            best_seed: BEST_SEED_PATH.to_string(),
+            checkpoint: CHECKPOINT_PATH.to_string(),
+            effective_config: EFFECTIVE_CONFIG_PATH.to_string(),
+            metrics: METRICS_PATH.to_string(),
+            pattern_placement: PATTERN_PLACEMENT.parse().expect("Invalid PATTERN_PLACEMENT in constants"),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: WINDOW_WIDTH_MAX,
+            height: WINDOW_HEIGHT_MAX,
+            generations_per_frame: GENERATIONS_PER_FRAME,
+            ticks_per_second: TICKS_PER_SECOND,
+            fixed_rate: FIXED_RATE,
+            hud: HUD,
+            grid_lines: GRID_LINES,
+        }
+    }
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            episodes: TRAINING_EPISODES,
+            output: TRAINING_OUTPUT.to_string(),
+            best_seeds: TRAINING_BEST_SEEDS,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+    // A "--set" argument which is not of the form section.key=value
+    Override(String),
+    // A setting outside of the range it can take
+    OutOfRange { setting: &'static str, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Write(e) => write!(f, "{}", e),
+            ConfigError::Override(s) => write!(f, "expected an override of the form section.key=value, found \"{}\"", s),
+            ConfigError::OutOfRange { setting, message } => write!(f, "{} {}", setting, message),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Write(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Write(e)
+    }
+}
+
+impl Config {
+    // Reads the file if there is one, applies the overrides on top of it and checks the result
+    // Overrides look like "ga.mutation_rate=0.3" or "grid.rule=B36/S23", values which are not valid TOML are taken as strings
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Self, ConfigError> {
+        let mut table = match path {
+            Some(path) => fs::read_to_string(path)?.parse::<toml::Value>()?,
+            None => toml::Value::Table(toml::value::Table::new()),
+        };
+
+        for setting in overrides {
+            apply_override(&mut table, setting)?;
+        }
+
+        let config: Config = table.try_into()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let out_of_range = |setting, message: &str| Err(ConfigError::OutOfRange { setting, message: message.to_string() });
+
+        let at_least_one = [
+            ("grid.columns", self.grid.columns as f32),
+            ("grid.rows", self.grid.rows as f32),
+            ("grid.max_population_age", self.grid.max_population_age as f32),
+            ("grid.max_cycle_length", self.grid.max_cycle_length as f32),
+            ("agent.hashlife_horizon", self.agent.hashlife_horizon as f32),
+            ("agent.hashlife_samples", self.agent.hashlife_samples as f32),
+            ("agent.max_state_space_size", self.agent.max_state_space_size as f32),
+            ("qlearning.placements", self.qlearning.placements as f32),
+            ("window.width", self.window.width),
+            ("window.height", self.window.height),
+            ("window.generations_per_frame", self.window.generations_per_frame as f32),
+        ];
+        for (setting, value) in at_least_one {
+            if value < 1.0 {
+                return out_of_range(setting, "must be at least 1");
+            }
+        }
+
+        let rates = [
+            ("agent.max_alive_ratio", self.agent.max_alive_ratio),
+            ("agent.epsilon", self.agent.epsilon),
+            ("agent.min_epsilon", self.agent.min_epsilon),
+            ("agent.max_epsilon", self.agent.max_epsilon),
+            ("ga.tournament_winners_percentage", self.ga.tournament_winners_percentage),
+            ("ga.selection_pressure", self.ga.selection_pressure),
+            ("ga.mutation_rate", self.ga.mutation_rate),
+            ("ga.crossover_rate", self.ga.crossover_rate),
+            ("ga.max_crossover_points", self.ga.max_crossover_points),
+            ("ga.max_crossover_section_size", self.ga.max_crossover_section_size),
+            ("ga.max_mutation_points", self.ga.max_mutation_points),
+            ("qlearning.learning_rate", self.qlearning.learning_rate),
+            ("qlearning.discount", self.qlearning.discount),
+        ];
+        for (setting, value) in rates {
+            if !(0.0..=1.0).contains(&value) {
+                return out_of_range(setting, "must be between 0 and 1");
+            }
+        }
+
+        // The GA picks its numbers of points from these ranges, so they cannot be empty
+        let above_zero = [
+            ("ga.max_crossover_points", self.ga.max_crossover_points),
+            ("ga.max_crossover_section_size", self.ga.max_crossover_section_size),
+            ("ga.max_mutation_points", self.ga.max_mutation_points),
+        ];
+        for (setting, value) in above_zero {
+            if value <= 0.0 {
+                return out_of_range(setting, "must be above 0");
+            }
+        }
+
+        // The viewer can slow a fixed rate down to a generation every four seconds
+        if self.window.ticks_per_second < 0.25 {
+            return out_of_range("window.ticks_per_second", "must be at least 0.25");
+        }
+
+        // Random states are at least 1% alive
+        if self.agent.max_alive_ratio < 0.01 {
+            return out_of_range("agent.max_alive_ratio", "must be at least 0.01");
+        }
+
+        if self.agent.seed.is_some_and(|seed| seed > MAX_SEED) {
+            return out_of_range("agent.seed", &format!("must be at most {}", MAX_SEED));
+        }
+
+        if self.agent.min_epsilon > self.agent.max_epsilon {
+            return out_of_range("agent.min_epsilon", "must not be above agent.max_epsilon");
+        }
+
+        for (setting, value) in [("agent.increase_factor", self.agent.increase_factor), ("agent.decrease_factor", self.agent.decrease_factor)] {
+            if value < 0.0 {
+                return out_of_range(setting, "must not be negative");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A seed for runs which were not given one, small enough to be saved with their config and read back
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen_range(0..=MAX_SEED)
+}
+
+fn apply_override(table: &mut toml::Value, setting: &str) -> Result<(), ConfigError> {
+    let invalid = || ConfigError::Override(setting.to_string());
+
+    let (path, value) = setting.split_once('=').ok_or_else(invalid)?;
+    let keys: Vec<&str> = path.trim().split('.').collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(invalid());
+    }
+
+    let value = value.trim();
+    let value = match format!("value = {}", value).parse::<toml::Value>() {
+        Ok(toml::Value::Table(mut parsed)) => parsed.remove("value").ok_or_else(invalid)?,
+        _ => toml::Value::String(value.to_string()),
+    };
+
+    let (last, sections) = keys.split_last().ok_or_else(invalid)?;
+    let mut current = table;
+    for key in sections {
+        current = current
+            .as_table_mut()
+            .ok_or_else(invalid)?
+            .entry(key.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+    }
+    current.as_table_mut().ok_or_else(invalid)?.insert(last.to_string(), value);
+
+    Ok(())
+}
+
+// Settings which have their own notation, e.g. rules, are written as strings in that notation
+mod display_from_str {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_configs_load_back_the_same() {
+        let path = std::env::temp_dir().join(format!("game_of_life_config_{}.toml", std::process::id()));
+
+        let mut config = Config::default();
+        config.grid.columns = 37;
+        config.grid.rule = "B36/S23".parse().unwrap();
+        config.grid.boundary = Boundary::KleinBottle;
+        config.agent.strategy = Strategy::QLearning;
+        config.ga.mutation_rate = 0.3;
+        config.window.grid_lines = true;
+
+        for seed in [random_seed(), random_seed(), 0, MAX_SEED] {
+            config.agent.seed = Some(seed);
+            config.save(&path).unwrap();
+            assert_eq!(Config::load(Some(&path), &[]).unwrap(), config);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overrides_set_the_largest_seed_and_reject_larger_ones() {
+        let config = Config::load(None, &[format!("agent.seed={}", MAX_SEED)]).unwrap();
+        assert_eq!(config.agent.seed, Some(MAX_SEED));
+
+        assert!(Config::load(None, &[format!("agent.seed={}", MAX_SEED + 1)]).is_err());
+
+        let mut config = Config::default();
+        config.agent.seed = Some(MAX_SEED + 1);
+        assert!(matches!(config.validate(), Err(ConfigError::OutOfRange { setting: "agent.seed", .. })));
+    }
+}
@@ -1,26 +1,100 @@
-// Constants for the grid
-pub const SCALE: f32 = 0.05;
+// The defaults for every setting of Config, each of which can be changed with a TOML config file or a --set override
+// The sections of the config file are noted next to each group
+
+// Constants for the grid [grid]
+pub const GRID_COLUMNS: usize = 20;
+pub const GRID_ROWS: usize = 20;
+
+// Where files are read and written [files]
+// Where the viewer saves the best seed when S is pressed, in the format named by the extension (.rle, .cells, .lif)
+pub const BEST_SEED_PATH: &str = "best_seed.rle";
+
+// Where the viewer saves its training checkpoint on exit or when C is pressed, and resumes it from at startup
+pub const CHECKPOINT_PATH: &str = "checkpoint.json";
+
+// Where the viewer writes the config it was started with, so the run can be reproduced
+pub const EFFECTIVE_CONFIG_PATH: &str = "effective_config.toml";
+
+// Where a record of every round of training is written, as CSV (.csv) or JSON Lines (.jsonl)
+// The train binary writes a file of the same name to its output directory instead
+pub const METRICS_PATH: &str = "metrics.csv";
+
+// The config file read at startup when it exists and no other file is given with --config
+pub const CONFIG_PATH: &str = "config.toml";
+
+// Defaults for the headless train binary [training]: how many rounds of training to run, where to write the results
+// and how many of the best seeds to write
+pub const TRAINING_EPISODES: usize = 1000;
+pub const TRAINING_OUTPUT: &str = "training";
+pub const TRAINING_BEST_SEEDS: usize = 10;
+
+// Where imported patterns go on the grid [files]: "centered", "top-left", or "reject" to refuse patterns which do not fit
+pub const PATTERN_PLACEMENT: &str = "centered";
+
+// Constants for the window [window]
 pub const WINDOW_WIDTH_MAX: f32 = 800.0;
 pub const WINDOW_HEIGHT_MAX: f32 = 800.0;
 
-// Constants for the Model
-pub const MAX_POPULATION_REPEATS: usize = 24;
+// How fast the viewer runs the simulation: a number of generations every frame,
+// or with FIXED_RATE a number of generations every second whatever the frame rate
+pub const GENERATIONS_PER_FRAME: usize = 1;
+pub const TICKS_PER_SECOND: f32 = 30.0;
+pub const FIXED_RATE: bool = false;
+
+// Whether the viewer starts out showing the agent's statistics over the grid, H shows or hides them
+pub const HUD: bool = true;
+
+// Whether the viewer outlines every cell, G shows or hides the outlines
+// They are only drawn while cells are big enough on screen for them to leave room for the cells
+pub const GRID_LINES: bool = false;
+
+// The Life-like rule in B/S notation [grid], e.g. "B3/S23" (Conway), "B36/S23" (HighLife), "B3678/S34678" (Day & Night)
+pub const RULE: &str = "B3/S23";
+
+// How the edges of the grid behave: "dead", "alive", "torus", "klein", "cross" or "reflect"
+pub const BOUNDARY: &str = "dead";
+
+// The simulator the agent evaluates states with: "grid" or "hashlife" [agent]
+// HashLife runs on the unbounded plane for up to HASHLIFE_HORIZON generations and ignores BOUNDARY
+pub const ENGINE: &str = "grid";
+pub const HASHLIFE_HORIZON: u64 = 1 << 16;
+pub const HASHLIFE_SAMPLES: u64 = 256;
+
+// Constants for the Model [grid]
 pub const MAX_POPULATION_AGE: usize = 2000;
 
-// Constants for the agent
+// How the agent scores each state's run [agent]: "classic", "lifespan", "final-population", "peak-population" or "bounding-box-growth"
+pub const FITNESS: &str = "classic";
+
+// Whether the agent keeps the per-generation statistics of every state it evaluates on the grid
+// Each run keeps up to MAX_POPULATION_AGE records, so this uses a lot of memory with a large state space
+pub const RECORD_HISTORY: bool = false;
+
+// Constants for the agent [agent]
+// How the agent searches for seeds: "evolve" explores random seeds or evolves the known ones with the GA,
+// "q-learning" places cells one at a time, learning which placements lead to the best runs
+pub const STRATEGY: &str = "evolve";
 pub const MAX_ALIVE_RATIO: f32 = 0.70;
 pub const INITIAL_PROBABILITY: f32 = 0.0;
 pub const MAX_STATE_SPACE_SIZE: usize = 820;
 
+// Number of threads the agent evaluates new states on, 0 uses every available core
+pub const EVALUATION_WORKERS: usize = 0;
+
+// Seed for every random choice the agent and GA make, the same seed and settings always evaluate the same states
+// None draws a new seed each run, which is then written into the effective config and checkpoints
+pub const SEED: Option<u64> = None;
+
 // Constants controlling exploration and exploitation
 pub const EPSILON: f32 = 0.2;
 pub const MAX_EPSILON : f32 = 0.8;
 pub const MIN_EPSILON : f32 = 0.05;
 pub const INCREASE_FACTOR : f32 = 200.0;
 pub const DECREASE_FACTOR : f32 = 100.0;
+// Longest period an oscillating pattern can have and still be detected as settled [grid]
 pub const MAX_CYCLE_LENGTH: usize = 24;
 
-// Constants for the GA
+// Constants for the GA [ga]
 pub const TOURNAMENT_WINNERS_PERCENTAGE: f32 = 0.70;
 pub const SELECTION_PRESSURE: f32 = 0.78;
 pub const MUTATION_RATE: f32 = 0.20;
@@ -28,3 +102,9 @@ pub const CROSSOVER_RATE: f32 = 0.72;
 pub const MAX_CROSSOVER_POINTS: f32 = 0.5;
 pub const MAX_CROSSOVER_SECTION_SIZE: f32 = 0.5;
 pub const MAX_MUTATION_POINTS: f32 = 0.3;
+
+// Constants for the q-learning strategy [qlearning]
+// Number of cells placed to build each seed, at most the number of cells of the grid
+pub const QLEARNING_PLACEMENTS: usize = 60;
+pub const QLEARNING_LEARNING_RATE: f32 = 0.05;
+pub const QLEARNING_DISCOUNT: f32 = 0.95;
@@ -0,0 +1,283 @@
+use std::fmt;
+use std::error::Error;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use bitvec::prelude::*;
+
+use crate::grid::{Grid, Termination};
+use crate::config::{Config, GridConfig, random_seed};
+use crate::fitness::{Fitness, Episode};
+use crate::hashlife::BoundingBox;
+
+// An environment for learners from outside the crate, in the style of Gym
+//
+// Each step may flip one cell of the grid, then advances it by one generation. The episode is done once the grid
+// dies out, settles into a cycle or reaches the maximum age, and only then is it rewarded, with the fitness of the
+// whole run. Every other step is rewarded 0.
+pub struct LifeEnv {
+    pub grid_config: GridConfig,
+    // Random starting states are up to this fraction alive
+    pub max_alive_ratio: f32,
+    pub fitness: Box<dyn Fitness>,
+
+    grid: Option<Grid>,
+    initial_bounding_box: Option<BoundingBox>,
+    done: bool,
+    rng: StdRng,
+}
+
+// Action 0 leaves the grid as it is, action i + 1 flips cell i, counting row by row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionSpace {
+    pub n: usize,
+}
+
+// Observations are the cells of the grid, row by row, each either alive or dead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservationSpace {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub cells: BitVec,
+    pub generation: usize,
+    pub population: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    // Why the episode ended, once it has
+    pub termination: Option<Termination>,
+    pub generation: usize,
+    pub population: usize,
+}
+
+// Why a step could not be taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvError {
+    // The action is not in the action space
+    InvalidAction { action: usize, n: usize },
+    // No episode has been started with reset or reset_to
+    NotReset,
+    // The episode is over, and reset has to be called to start another
+    Done,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::InvalidAction { action, n } => write!(f, "expected an action below {}, found {}", n, action),
+            EnvError::NotReset => write!(f, "expected reset to be called before step"),
+            EnvError::Done => write!(f, "expected reset to be called after the episode was done"),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+impl ActionSpace {
+    pub fn contains(&self, action: usize) -> bool {
+        action < self.n
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        rng.gen_range(0..self.n)
+    }
+}
+
+impl ObservationSpace {
+    pub fn num_cells(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    pub fn contains(&self, observation: &Observation) -> bool {
+        observation.cells.len() == self.num_cells()
+    }
+}
+
+impl LifeEnv {
+    // The grid, the random starting states and the seed of the environment's random numbers all come from the config
+    pub fn new(config: &Config, fitness: Box<dyn Fitness>) -> Self {
+        LifeEnv {
+            grid_config: config.grid.clone(),
+            max_alive_ratio: config.agent.max_alive_ratio,
+            fitness,
+            grid: None,
+            initial_bounding_box: None,
+            done: false,
+            rng: StdRng::seed_from_u64(config.agent.seed.unwrap_or_else(random_seed)),
+        }
+    }
+
+    pub fn action_space(&self) -> ActionSpace {
+        ActionSpace { n: self.grid_config.columns * self.grid_config.rows + 1 }
+    }
+
+    pub fn observation_space(&self) -> ObservationSpace {
+        ObservationSpace { columns: self.grid_config.columns, rows: self.grid_config.rows }
+    }
+
+    // Starts an episode from a random state
+    // A seed restarts the environment's random numbers from it, otherwise they carry on from the last episode
+    pub fn reset(&mut self, seed: Option<u64>) -> Observation {
+        if let Some(seed) = seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+
+        let alive_ratio = self.rng.gen_range(0.01..=self.max_alive_ratio);
+        let num_cells = self.grid_config.columns * self.grid_config.rows;
+        let state: BitVec = (0..num_cells).map(|_| self.rng.gen::<f32>() < alive_ratio).collect();
+
+        self.reset_to(&state)
+    }
+
+    // Starts an episode from the given state, laid out row by row
+    // Even a state which is already dead gets a first step, in which a cell may be brought to life
+    pub fn reset_to(&mut self, state: &BitVec) -> Observation {
+        let grid = Grid::new(&self.grid_config, state);
+        self.initial_bounding_box = grid.bits.bounding_box();
+        self.grid = Some(grid);
+        self.done = false;
+
+        self.observation()
+    }
+
+    // Returns the observation after the step, its reward, whether the episode is done, and what happened
+    // An action outside the action space, or a step without an episode in progress, leaves the environment as it was
+    pub fn step(&mut self, action: usize) -> Result<(Observation, f32, bool, StepInfo), EnvError> {
+        let n = self.action_space().n;
+        if action >= n {
+            return Err(EnvError::InvalidAction { action, n });
+        }
+        if self.done {
+            return Err(EnvError::Done);
+        }
+        let grid = self.grid.as_mut().ok_or(EnvError::NotReset)?;
+
+        if action > 0 {
+            let (x, y) = ((action - 1) % grid.columns, (action - 1) / grid.columns);
+            let alive = grid.is_alive(x, y);
+            grid.set(x, y, !alive);
+        }
+        grid.update();
+
+        let termination = grid.termination();
+        self.done = termination.is_some();
+        let info = StepInfo { termination, generation: grid.population_age, population: grid.population };
+
+        let reward = match termination {
+            Some(_) => self.fitness.score(&Episode::from_grid(grid.clone(), self.initial_bounding_box)),
+            None => 0.0,
+        };
+
+        Ok((self.observation(), reward, self.done, info))
+    }
+
+    fn observation(&self) -> Observation {
+        let grid = self.grid.as_ref().expect("Expected reset to be called first");
+        Observation { cells: grid.bits.to_state(), generation: grid.population_age, population: grid.population }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitness::BuiltinFitness;
+
+    fn env(columns: usize, rows: usize) -> LifeEnv {
+        let mut config = Config::default();
+        config.grid.columns = columns;
+        config.grid.rows = rows;
+        config.agent.seed = Some(0);
+        LifeEnv::new(&config, Box::new(BuiltinFitness::FinalPopulation))
+    }
+
+    fn state(columns: usize, rows: usize, cells: &[(usize, usize)]) -> BitVec {
+        let mut state = bitvec![0; columns * rows];
+        for &(x, y) in cells {
+            state.set(y * columns + x, true);
+        }
+        state
+    }
+
+    #[test]
+    fn reset_gives_observations_of_the_observation_space() {
+        let mut env = env(12, 7);
+        let first = env.reset(Some(3));
+
+        assert!(env.observation_space().contains(&first));
+        assert_eq!(first.generation, 0);
+        assert_eq!(first.population, first.cells.count_ones());
+        assert_eq!(env.action_space().n, 12 * 7 + 1);
+
+        // The same seed starts the same episode, and carrying on without one starts another
+        assert_eq!(env.reset(Some(3)), first);
+        assert_ne!(env.reset(None), first);
+    }
+
+    #[test]
+    fn actions_flip_one_cell_before_the_generation_runs() {
+        let mut env = env(6, 6);
+
+        // Three cells of a block, which the action completes so it stays as it is, ending the episode straight away
+        env.reset_to(&state(6, 6, &[(2, 2), (3, 2), (2, 3)]));
+        let (observation, reward, done, info) = env.step(3 * 6 + 3 + 1).unwrap();
+
+        assert_eq!(observation.cells, state(6, 6, &[(2, 2), (3, 2), (2, 3), (3, 3)]));
+        assert_eq!((done, info.generation, info.population), (true, 1, 4));
+        assert_eq!(info.termination, Some(Termination::Cycle { period: 1 }));
+        assert_eq!(reward, 4.0 / 36.0);
+    }
+
+    #[test]
+    fn only_the_last_step_is_rewarded() {
+        let mut env = env(8, 8);
+
+        // A blinker is found to cycle once it has been seen twice
+        env.reset_to(&state(8, 8, &[(3, 4), (4, 4), (5, 4)]));
+        let mut steps = Vec::new();
+        loop {
+            let (_, reward, done, info) = env.step(0).unwrap();
+            steps.push((reward, info));
+            if done {
+                break;
+            }
+        }
+
+        let (last_reward, last_info) = steps.pop().unwrap();
+        assert!(steps.iter().all(|&(reward, info)| reward == 0.0 && info.termination.is_none()));
+        assert_eq!(last_info.termination, Some(Termination::Cycle { period: 2 }));
+        assert_eq!(last_reward, 3.0 / 64.0);
+    }
+
+    #[test]
+    fn dying_out_ends_the_episode() {
+        let mut env = env(5, 5);
+
+        env.reset_to(&state(5, 5, &[(2, 2)]));
+        let (observation, _, done, info) = env.step(0).unwrap();
+
+        assert!(done);
+        assert_eq!(observation.population, 0);
+        assert_eq!(info.termination, Some(Termination::Extinct));
+    }
+
+    #[test]
+    fn steps_which_cannot_be_taken_are_errors() {
+        let mut env = env(5, 5);
+        assert_eq!(env.step(0), Err(EnvError::NotReset));
+
+        env.reset_to(&state(5, 5, &[(2, 2)]));
+        assert_eq!(env.step(26), Err(EnvError::InvalidAction { action: 26, n: 26 }));
+
+        // A rejected action does not use up the step
+        assert!(env.step(0).unwrap().2);
+        assert_eq!(env.step(0), Err(EnvError::Done));
+
+        assert!(env.reset(None).cells.len() == 25);
+        assert!(env.step(0).is_ok());
+    }
+}
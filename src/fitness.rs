@@ -0,0 +1,143 @@
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::grid::{Grid, Termination};
+use crate::census::Census;
+use crate::stats::GenerationStats;
+use crate::hashlife::BoundingBox;
+
+// Everything known about how a state's run went, for a fitness function to score
+pub struct Episode {
+    pub termination: Termination,
+    // The generation the run settled at: when it died out, when its cycle started, or the last one it reached
+    pub generations: usize,
+    // The longest a run can go on for on the engine it was run with
+    pub max_generations: usize,
+    pub num_cells: usize,
+    pub initial_population: usize,
+    pub final_population: usize,
+    pub peak_population: usize,
+    pub standard_deviation: f32,
+    pub initial_bounding_box: Option<BoundingBox>,
+    pub final_bounding_box: Option<BoundingBox>,
+    pub census: Census,
+    // The statistics of every generation, when the agent records them
+    pub history: Option<Vec<GenerationStats>>,
+    // The grid as it was at the end of the run, unless the run was on the unbounded plane with HashLife
+    pub grid: Option<Grid>,
+}
+
+impl Episode {
+    // Everything about a grid's run once it has finished, given the bounding box its live cells had at the start
+    pub fn from_grid(mut grid: Grid, initial_bounding_box: Option<BoundingBox>) -> Self {
+        Episode {
+            termination: grid.termination().expect("Expected the grid to have finished"),
+            // The generation the grid settled at is its age, unless it ended in a cycle
+            generations: grid.cycle_start.unwrap_or(grid.population_age),
+            max_generations: grid.max_population_age,
+            num_cells: grid.num_cells,
+            initial_population: grid.initial_population,
+            final_population: grid.final_population,
+            peak_population: grid.peak_population,
+            standard_deviation: grid.standard_deviation,
+            initial_bounding_box,
+            final_bounding_box: grid.bits.bounding_box(),
+            census: Census::from_grid(&grid),
+            history: grid.history.take(),
+            grid: Some(grid),
+        }
+    }
+}
+
+// Scores an episode, higher being better
+// States are evaluated on several threads at once, so fitness functions have to be shareable between them
+pub trait Fitness: Send + Sync {
+    fn score(&self, episode: &Episode) -> f32;
+}
+
+// The fitness functions which come with the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinFitness {
+    // Rewards growing populations which live long and fluctuate a lot
+    Classic,
+    // The longer a run goes on before settling the better
+    Lifespan,
+    FinalPopulation,
+    PeakPopulation,
+    // How much larger the area covered by the live cells has become
+    BoundingBoxGrowth,
+}
+
+impl Fitness for BuiltinFitness {
+    fn score(&self, episode: &Episode) -> f32 {
+        let num_cells = episode.num_cells as f32;
+
+        match self {
+            BuiltinFitness::Classic => {
+                let population_difference = (episode.final_population as f32 - episode.initial_population as f32) / num_cells;
+                let population_age = episode.generations as f32 / episode.max_generations as f32;
+
+                // Including the standard deviation will encourage the agent to explore states which have more
+                // dynamic population fluctuations
+                let standard_deviation = episode.standard_deviation / num_cells;
+
+                // Calculate a scaled difference using an exponential function
+                // This will ensure that positive differences are amplified and negative differences are diminished
+                let scaled_difference = 1.0 / (1.0 + f32::exp(-population_difference));
+
+                (scaled_difference * population_age * standard_deviation).clamp(0.0, 1.0)
+            }
+            BuiltinFitness::Lifespan => episode.generations as f32 / episode.max_generations as f32,
+            BuiltinFitness::FinalPopulation => episode.final_population as f32 / num_cells,
+            BuiltinFitness::PeakPopulation => episode.peak_population as f32 / num_cells,
+            BuiltinFitness::BoundingBoxGrowth => {
+                let area = |bbox: Option<BoundingBox>| bbox.map_or(0.0, |bbox| (bbox.width() * bbox.height()) as f32);
+                ((area(episode.final_bounding_box) - area(episode.initial_bounding_box)) / num_cells).max(0.0)
+            }
+        }
+    }
+}
+
+impl fmt::Display for BuiltinFitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BuiltinFitness::Classic => "classic",
+            BuiltinFitness::Lifespan => "lifespan",
+            BuiltinFitness::FinalPopulation => "final-population",
+            BuiltinFitness::PeakPopulation => "peak-population",
+            BuiltinFitness::BoundingBoxGrowth => "bounding-box-growth",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFitnessError(pub String);
+
+impl fmt::Display for ParseFitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown fitness \"{}\", expected \"classic\", \"lifespan\", \"final-population\", \"peak-population\" or \"bounding-box-growth\"",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseFitnessError {}
+
+impl FromStr for BuiltinFitness {
+    type Err = ParseFitnessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "classic" => Ok(BuiltinFitness::Classic),
+            "lifespan" => Ok(BuiltinFitness::Lifespan),
+            "final-population" => Ok(BuiltinFitness::FinalPopulation),
+            "peak-population" => Ok(BuiltinFitness::PeakPopulation),
+            "bounding-box-growth" => Ok(BuiltinFitness::BoundingBoxGrowth),
+            _ => Err(ParseFitnessError(s.to_string())),
+        }
+    }
+}
@@ -1,30 +1,45 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use rand::prelude::*;
 use bitvec::prelude::*;
 
-use crate::constants::{MAX_CROSSOVER_POINTS, MAX_CROSSOVER_SECTION_SIZE, MAX_MUTATION_POINTS};
+use crate::config::GAConfig;
 
 pub struct GA {
-    tournament_winners_percentage: f32,
-    selection_pressure: f32,
-    mutation_rate: f32,
-    crossover_rate: f32,
+    pub tournament_winners_percentage: f32,
+    pub selection_pressure: f32,
+    pub mutation_rate: f32,
+    pub crossover_rate: f32,
+
+    // Upper bounds, as fractions of the grid, of how much crossover and mutation change a state
+    pub max_crossover_points: f32,
+    pub max_crossover_section_size: f32,
+    pub max_mutation_points: f32,
+
+    // Shape of the grid the states are laid out on, row by row
+    columns: usize,
+    rows: usize,
 }
 
 impl GA {
-    pub fn new(tournament_winners_percentage: f32, selection_pressure: f32, mutation_rate: f32, crossover_rate: f32) -> Self {
+    pub fn new(config: &GAConfig, columns: usize, rows: usize) -> Self {
         GA { 
-            tournament_winners_percentage, 
-            selection_pressure, 
-            mutation_rate, 
-            crossover_rate,
+            tournament_winners_percentage: config.tournament_winners_percentage, 
+            selection_pressure: config.selection_pressure, 
+            mutation_rate: config.mutation_rate, 
+            crossover_rate: config.crossover_rate,
+            max_crossover_points: config.max_crossover_points,
+            max_crossover_section_size: config.max_crossover_section_size,
+            max_mutation_points: config.max_mutation_points,
+            columns,
+            rows,
         }
     }
 
-    pub fn evolve(&self, population: &HashMap<BitVec, f32>) -> Option<Vec<BitVec>> {
+    // Every random choice is drawn from rng, and the population is ordered, so the same rng always gives the same states
+    pub fn evolve(&self, population: &BTreeMap<BitVec, f32>, rng: &mut impl Rng) -> Option<Vec<BitVec>> {
         // Perform tournament selection to get the best states
-        let tournament_winners = match self.tournament_selection(population) {
+        let tournament_winners = match self.tournament_selection(population, rng) {
             Some(winners) => winners,
             None => {
                 return None;
@@ -33,7 +48,7 @@ impl GA {
         };
 
         // Perform crossover to get the new states
-        let mut new_states = match self.crossover(&tournament_winners) {
+        let mut new_states = match self.crossover(&tournament_winners, rng) {
             Some(states) => states,
             None => {
                 return None;
@@ -41,7 +56,7 @@ impl GA {
         };
 
         // Perform mutation on the new states
-        match self.mutate(&mut new_states) {
+        match self.mutate(&mut new_states, rng) {
             Some(_) => (),
             None => {
                 return None;
@@ -51,10 +66,9 @@ impl GA {
         Some(new_states)
     }
 
-    fn tournament_selection(&self, population: &HashMap<BitVec, f32>) -> Option<HashMap<BitVec, f32>> {
-        let mut rng = thread_rng();
+    fn tournament_selection(&self, population: &BTreeMap<BitVec, f32>, rng: &mut impl Rng) -> Option<BTreeMap<BitVec, f32>> {
         let population_size = population.len();
-        let mut winners: HashMap<BitVec, f32> = HashMap::new();
+        let mut winners: BTreeMap<BitVec, f32> = BTreeMap::new();
         let number_of_winners = (population_size as f32 * self.tournament_winners_percentage).ceil() as usize;
 
         // Break the population into a queue of batches where each batch will compete in a tournament
@@ -73,7 +87,7 @@ impl GA {
             return None;
         }
 
-        // Perform tournament selection on each batch and add the winner to the winners HashMap
+        // Perform tournament selection on each batch and add the winner to the winners map
         for i in 0..number_of_winners {
             // Using selection pressure, decide if the fittest will win or a random individual
             let winner_index = if rng.gen::<f32>() > self.selection_pressure {
@@ -100,20 +114,20 @@ impl GA {
                     fittest_index
                 };
 
-            // Add the winner to the winners HashMap
+            // Add the winner to the winners map
             winners.insert(queue[i][winner_index].clone(), population[&queue[i][winner_index]]);
         }
 
         Some(winners)
     }
 
-    fn get_queue(&self, population: &HashMap<BitVec, f32>, number_of_winners: usize, population_size: usize) -> Option<Vec<Vec<BitVec>>> {
+    fn get_queue(&self, population: &BTreeMap<BitVec, f32>, number_of_winners: usize, population_size: usize) -> Option<Vec<Vec<BitVec>>> {
         // If number_of_winners is 0 or if it is greater than the population size, return an error
         if number_of_winners == 0 || number_of_winners > population_size {
             return None;
         }
 
-        // Collect the keys (BitVecs) of the population HashMap into a vector
+        // Collect the keys (BitVecs) of the population into a vector, in order
         let population_keys: Vec<&BitVec> = population.keys().collect();
 
         // Divide the population into number_of_winners many batches - Each batch will compete in a tournament 
@@ -143,7 +157,7 @@ impl GA {
         Some(queue)
     }
 
-    fn crossover(&self, tournament_winners: &HashMap<BitVec, f32>) -> Option<Vec<BitVec>> {
+    fn crossover(&self, tournament_winners: &BTreeMap<BitVec, f32>, rng: &mut impl Rng) -> Option<Vec<BitVec>> {
         // Perform crossover at a rate equal to crossover_rate on the tournament winners to get the new states
         // Returns Vec<BitVec> since these are new states which haven't been evaluated yet
 
@@ -155,13 +169,11 @@ impl GA {
         // We will iterate through each winner and based on crossover rate, that winner will either stay as it is
         // or it will be replaced by a new state which is a crossover of itself and another winner
 
-        let mut rng = thread_rng();
         let num_states = tournament_winners.len();
-        let grid_size = tournament_winners.keys().next()?.len();
-        let grid_side_length = (grid_size as f32).sqrt() as usize;
+        let grid_size = self.columns * self.rows;
 
-        // If grid_side_length is 0, return None and print an error
-        if grid_side_length == 0 {
+        // If the grid is empty or the states do not match its shape, return None
+        if grid_size == 0 || tournament_winners.keys().any(|state| state.len() != grid_size) {
             return None;
         }
 
@@ -173,38 +185,38 @@ impl GA {
             } else {
                 // Here we will perform crossover between the current state and another state
                 // We will choose the other state randomly and confirm that it is not the same as the current state
-                let other_state_index = (0..num_states).filter(|&x| x != i).choose(&mut rng).unwrap();
+                let other_state_index = (0..num_states).filter(|&x| x != i).choose(rng).unwrap();
                 let other_state = tournament_winners.keys().nth(other_state_index).unwrap();
     
                 // Clone the parent state to start with
                 let mut new_state = parent_state.clone();
 
-                // We begin by selecting a random percentage between 1 and MAX_CROSSOVER_POINTS
+                // We begin by selecting a random percentage between 0 and max_crossover_points
                 // This percentage will be used to determine the number of crossover points
-                let percentage = rng.gen_range(0.0..MAX_CROSSOVER_POINTS);
+                let percentage = rng.gen_range(0.0..self.max_crossover_points);
 
                 // We then calculate the number of crossover points based on the percentage
                 let num_crossover_points = (percentage * grid_size as f32).ceil() as usize;
 
                 // Next, we calculate the dimensions of each crossover section
-                // This is equal to the side length of the grid multiplied by a random percentage between 0 and MAX_CROSSOVER_SECTION_SIZE
-                // We got the grid side length by taking the square root of the length of one of the state
-                let crossover_size_percentage = rng.gen_range(0.0..MAX_CROSSOVER_SECTION_SIZE);
-                let crossover_side_length = (grid_side_length as f32 * crossover_size_percentage).ceil() as usize;
+                // These are the width and height of the grid multiplied by a random percentage between 0 and max_crossover_section_size
+                let crossover_size_percentage = rng.gen_range(0.0..self.max_crossover_section_size);
+                let crossover_width = ((self.columns as f32 * crossover_size_percentage).ceil() as usize).max(1);
+                let crossover_height = ((self.rows as f32 * crossover_size_percentage).ceil() as usize).max(1);
     
                 // Next, we will iterate through each crossover point and perform crossover
-                // This will require us to construct each rectangular crossover section based on the crossover_side_length,
+                // This will require us to construct each rectangular crossover section based on the crossover width and height,
                 // the crossover point, and its distance from the edges of the grid
                 // and we will contruct the new state as a composite of the crossover sections
                 for _ in 0..num_crossover_points {
-                    let point_x = rng.gen_range(0..grid_side_length);
-                    let point_y = rng.gen_range(0..grid_side_length);
-                    let max_section_size = grid_side_length - point_x.max(point_y);
-                    let crossover_size = rng.gen_range(1..=max_section_size.min(crossover_side_length));
+                    let point_x = rng.gen_range(0..self.columns);
+                    let point_y = rng.gen_range(0..self.rows);
+                    let section_width = rng.gen_range(1..=(self.columns - point_x).min(crossover_width));
+                    let section_height = rng.gen_range(1..=(self.rows - point_y).min(crossover_height));
     
-                    for y in point_y..(point_y + crossover_size).min(grid_side_length) {
-                        for x in point_x..(point_x + crossover_size).min(grid_side_length) {
-                            let index = y * grid_side_length + x;
+                    for y in point_y..point_y + section_height {
+                        for x in point_x..point_x + section_width {
+                            let index = y * self.columns + x;
                             new_state.set(index, other_state[index]);
                         }
                     }
@@ -217,13 +229,12 @@ impl GA {
         Some(new_states)
     }
 
-    fn mutate(&self, new_states: &mut Vec<BitVec>) -> Option<()> {
+    fn mutate(&self, new_states: &mut [BitVec], rng: &mut impl Rng) -> Option<()> {
         // If new_states is empty, return None
         if new_states.is_empty() {
             return None;
         }
 
-        let mut rng = thread_rng();
         let num_states = new_states.len();
         let state_size = new_states[0].len();
 
@@ -238,7 +249,7 @@ impl GA {
                 continue;
             } else {
                 // Calculate the number of mutation points
-                let percentage = rng.gen_range(0.0..MAX_MUTATION_POINTS);
+                let percentage = rng.gen_range(0.0..self.max_mutation_points);
                 let num_mutation_points = (percentage * state_size as f32).ceil() as usize;
 
                 // Mutate the state at the mutation points
@@ -254,4 +265,164 @@ impl GA {
 
         Some(())
     }
-}
\ No newline at end of file
+}
+
+// How different the states of a population are from each other: the fraction of cells two distinct states differ in, on average
+// Each cell alive in k of the n states differs between k * (n - k) of the pairs, which avoids comparing every pair
+pub fn diversity(population: &BTreeMap<BitVec, f32>) -> f32 {
+    let num_states = population.len();
+    let num_cells = population.keys().next().map_or(0, |state| state.len());
+    if num_states < 2 || num_cells == 0 {
+        return 0.0;
+    }
+
+    let mut alive = vec![0usize; num_cells];
+    for state in population.keys() {
+        for index in state.iter_ones().filter(|&index| index < num_cells) {
+            alive[index] += 1;
+        }
+    }
+
+    let differing_pairs: usize = alive.iter().map(|&k| k * (num_states - k)).sum();
+    let pairs = num_states * (num_states - 1) / 2;
+
+    differing_pairs as f32 / (pairs * num_cells) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Crossover only happens when the random draw is not below crossover_rate, so a rate of 0 always crosses over
+    fn ga(columns: usize, rows: usize) -> GA {
+        let config = GAConfig {
+            tournament_winners_percentage: 0.7,
+            selection_pressure: 0.78,
+            mutation_rate: 1.0,
+            crossover_rate: 0.0,
+            ..GAConfig::default()
+        };
+        GA::new(&config, columns, rows)
+    }
+
+    // The cells set in a state, if they fill a rectangle of the grid without wrapping from one row to the next
+    fn rectangle(state: &BitVec, columns: usize) -> Option<(usize, usize)> {
+        let cells: Vec<(usize, usize)> = state.iter_ones().map(|index| (index % columns, index / columns)).collect();
+        let (min_x, max_x) = (cells.iter().map(|c| c.0).min()?, cells.iter().map(|c| c.0).max()?);
+        let (min_y, max_y) = (cells.iter().map(|c| c.1).min()?, cells.iter().map(|c| c.1).max()?);
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+
+        (cells.len() == width * height).then_some((width, height))
+    }
+
+    #[test]
+    fn crossover_keeps_offspring_inside_rectangular_grids() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for &(columns, rows) in &[(7, 3), (3, 11), (1, 9), (9, 1), (40, 20)] {
+            let num_cells = columns * rows;
+
+            // A single crossover section per offspring, so what each parent receives from the other is one rectangle
+            let mut ga = ga(columns, rows);
+            ga.max_crossover_points = 1e-6;
+            let max_width = (columns as f32 * ga.max_crossover_section_size).ceil() as usize;
+            let max_height = (rows as f32 * ga.max_crossover_section_size).ceil() as usize;
+
+            // One parent is all dead, the other all alive, so every cell an offspring took from the other parent shows
+            let dead = bitvec![0; num_cells];
+            let mut winners = BTreeMap::new();
+            winners.insert(dead.clone(), 0.5);
+            winners.insert(bitvec![1; num_cells], 0.5);
+
+            for _ in 0..200 {
+                let offspring = ga.crossover(&winners, &mut rng).expect("Expected crossover to succeed");
+                assert_eq!(offspring.len(), 2);
+
+                for (parent, state) in winners.keys().zip(offspring) {
+                    assert_eq!(state.len(), num_cells);
+
+                    let taken = if *parent == dead { state } else { !state };
+                    if taken.not_any() {
+                        continue;
+                    }
+
+                    // A section which ran past the right edge would wrap onto the next row, and no longer be a rectangle
+                    let (width, height) = rectangle(&taken, columns)
+                        .unwrap_or_else(|| panic!("{}x{}: cells taken from the other parent are not a rectangle: {}", columns, rows, taken));
+                    assert!(width <= max_width.max(1) && height <= max_height.max(1), "{}x{}: a {}x{} section", columns, rows, width, height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_reaches_every_cell_of_a_rectangular_grid() {
+        let (columns, rows) = (12, 5);
+        let num_cells = columns * rows;
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let dead = bitvec![0; num_cells];
+        let mut winners = BTreeMap::new();
+        winners.insert(dead.clone(), 0.5);
+        winners.insert(bitvec![1; num_cells], 0.5);
+
+        // Collect every cell the dead parent's offspring received from the alive parent
+        let mut reached = bitvec![0; num_cells];
+        for _ in 0..2000 {
+            // Offspring come out in the same order as their parents
+            let offspring = ga(columns, rows).crossover(&winners, &mut rng).unwrap();
+            for (parent, state) in winners.keys().zip(offspring) {
+                if *parent == dead {
+                    reached |= state;
+                }
+            }
+        }
+
+        // Treating the grid as a square would never touch the columns past the square's side
+        assert!(reached.all(), "cells never crossed over: {:?}", reached.iter_zeros().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn crossover_rejects_states_of_the_wrong_shape() {
+        let mut winners = BTreeMap::new();
+        winners.insert(bitvec![0; 20 * 20], 0.5);
+        winners.insert(bitvec![1; 20 * 20], 0.5);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(ga(30, 10).crossover(&winners, &mut rng).is_none());
+    }
+
+    #[test]
+    fn evolve_keeps_offspring_inside_non_square_grids() {
+        let (columns, rows) = (16, 4);
+        let num_cells = columns * rows;
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut population = BTreeMap::new();
+        while population.len() < 40 {
+            let state: BitVec = (0..num_cells).map(|_| rng.gen_bool(0.4)).collect();
+            population.insert(state, rng.gen::<f32>());
+        }
+
+        for _ in 0..50 {
+            let offspring = ga(columns, rows).evolve(&population, &mut rng).expect("Expected evolution to succeed");
+            assert!(offspring.iter().all(|state| state.len() == num_cells));
+        }
+    }
+
+    #[test]
+    fn evolve_gives_the_same_offspring_for_the_same_seed() {
+        let (columns, rows) = (10, 6);
+        let num_cells = columns * rows;
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let mut population = BTreeMap::new();
+        while population.len() < 30 {
+            let state: BitVec = (0..num_cells).map(|_| rng.gen_bool(0.4)).collect();
+            population.insert(state, rng.gen::<f32>());
+        }
+
+        let evolve = |seed| ga(columns, rows).evolve(&population, &mut StdRng::seed_from_u64(seed)).unwrap();
+        assert_eq!(evolve(1), evolve(1));
+        assert_ne!(evolve(1), evolve(2));
+    }
+}
@@ -1,126 +1,148 @@
-use nannou::prelude::*;
+use std::fmt;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use bitvec::prelude::*;
 
-use crate::cell::Cell;
-use crate::constants::{SCALE, MAX_CYCLE_LENGTH};
+use crate::rule::Rule;
+use crate::boundary::Boundary;
+use crate::bitgrid::BitGrid;
+use crate::stats::GenerationStats;
+use crate::config::GridConfig;
+
+// Why an episode came to an end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Extinct,
+    // The population repeats itself every `period` generations
+    Cycle { period: usize },
+    // The episode ran for as long as it was allowed to without settling
+    MaxAge,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Extinct => write!(f, "died out"),
+            Termination::Cycle { period: 1 } => write!(f, "became still"),
+            Termination::Cycle { period } => write!(f, "settled into a period {} cycle", period),
+            Termination::MaxAge => write!(f, "reached the maximum age"),
+        }
+    }
+}
 
+// The simulation only, mapping cells onto the screen is left to the viewer
+#[derive(Clone)]
 pub struct Grid {
-    pub cells: Vec<Cell>,
+    // Packed cell states which do the actual stepping
+    pub bits: BitGrid,
     pub columns: usize,
     pub rows: usize,
-    pub cell_width: f32,
-    pub cell_height: f32,
     pub num_cells: usize,
+    pub rule: Rule,
+    pub boundary: Boundary,
+
+    // The episode ends after this many generations, and only cycles up to this long are detected
+    pub max_population_age: usize,
+    pub max_cycle_length: usize,
+
     pub population: usize,
     pub population_age: usize,
     pub population_mean: f32,
     pub sum_sq_diff: f32,
     pub standard_deviation: f32,
 
-    pub cycle_sum: usize,
-    pub cycle_average: f32,
+    // The most recent states with their hash and the generation they were seen at, used to detect exact cycles
+    pub recent_states: VecDeque<(u64, usize, BitGrid)>,
 
-    // Store the initializing bit vector for the grid
-    pub grid_state: BitVec,
+    // Once the grid repeats a previous state: the length of the cycle and the generation it started at
+    pub period: Option<usize>,
+    pub cycle_start: Option<usize>,
 
     // Track the initial and final population of the grid
     pub initial_population: usize,
     pub final_population: usize,
+    pub peak_population: usize,
+
+    // Statistics of every generation since record_history was called
+    pub history: Option<Vec<GenerationStats>>,
 }
 
 impl Grid {
-    pub fn new(window_width: f32, window_height: f32, grid_state: &BitVec) -> Self {
-        // Calculate grid dimensions
-        let cell_width = SCALE * window_width;
-        let cell_height = SCALE * window_height;
-        let cols = f32::floor(window_width / cell_width) as usize;
-        let rows = f32::floor(window_height / cell_height) as usize;
-        let x_padding = (window_width - (cols as f32 * cell_width)) / 2.0;
-        let y_padding = (window_height - (rows as f32 * cell_height)) / 2.0;
-
-        // Initialize grid
-        let num_cells = cols * rows;
-        let mut cells = Vec::with_capacity(num_cells);
-        let mut population = 0;
+    pub fn new(config: &GridConfig, grid_state: &BitVec) -> Self {
+        // Initialize grid from the state, which is laid out row by row
+        let (columns, rows) = (config.columns, config.rows);
+        let num_cells = columns * rows;
+        let bits = BitGrid::new(columns, rows, grid_state);
+        let population = bits.population();
         let population_age = 0;
 
-        // Set positions for cells based on the dimensions of the grid and the values in the grid_state
-        for y in 0..rows {
-            for x in 0..cols {
-                let x_pos = x_padding + x as f32 * cell_width - window_width / 2.0 + cell_width / 2.0;
-                let y_pos = y_padding+ y as f32 * cell_height - window_height / 2.0 + cell_height / 2.0;
-                let pos = pt2(x_pos, y_pos);
-                let idx = y * cols + x;
-
-                let state = grid_state[idx];
-
-                if state {
-                    population += 1;
-                }
-
-                cells.push(Cell { pos, state });
-            }
-        }
+        let recent_states = VecDeque::from([(hash_state(&bits), 0, bits.clone())]);
 
         Grid { 
-            cells, 
-            columns: cols, 
+            bits,
+            columns, 
             rows, 
-            cell_width, 
-            cell_height, 
             num_cells, 
+            rule: config.rule,
+            boundary: config.boundary,
+            max_population_age: config.max_population_age,
+            max_cycle_length: config.max_cycle_length,
             population, 
             population_age, 
             population_mean: 0.0,
-            cycle_average: 0.0,
-            cycle_sum: 0,
+            recent_states,
+            period: None,
+            cycle_start: None,
             sum_sq_diff: 0.0,
             standard_deviation: 0.0,
-            grid_state: grid_state.clone(), 
             initial_population: population, 
-            final_population: 0 
+            final_population: 0,
+            peak_population: population,
+            history: None,
         }
     }
 
-    // This is solely the logic for the Game of Life 
-    pub fn update(&mut self) {
-        // This population has lived to see another day!
-        self.population_age += 1;
+    // Starts keeping the statistics of every generation, beginning with the current one
+    pub fn record_history(&mut self) {
+        self.history = Some(vec![GenerationStats::new(self.population_age, &self.bits, None)]);
+    }
 
-        let mut new_states = vec![false; self.num_cells];
+    pub fn is_alive(&self, x: usize, y: usize) -> bool {
+        self.bits.get(x, y)
+    }
 
-        // Calculate the average population size over the last MAX_CYCLE_LENGTH cycles
-        // This tracks if the population is repeating in a cycle
-        if self.population_age % MAX_CYCLE_LENGTH == 0 {
-            self.cycle_average = self.cycle_sum as f32 / MAX_CYCLE_LENGTH as f32;
-            self.cycle_sum = 0;
-        } else {
-            self.cycle_sum += self.population;
+    // Changes a cell between generations
+    // The run no longer follows from the earlier generations, so cycles are only looked for from this one on
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        if x >= self.columns || y >= self.rows || self.bits.get(x, y) == alive {
+            return;
         }
 
-        for y in 0..self.rows {
-            for x in 0..self.columns {
-                let idx = y * self.columns + x;
-                let cell = &self.cells[idx];
-                let live_neighbors = self.count_live_neighbors(x, y);
-
-                new_states[idx] = match (cell.state, live_neighbors) {
-                    (true, 2) | (true, 3) => true,
-                    (true, _) => false,
-                    (false, 3) => true,
-                    (false, _) => false,
-                };
-            }
-        }
+        self.bits.set(x, y, alive);
+        self.population = self.bits.population();
+        self.final_population = self.population;
+        self.peak_population = self.peak_population.max(self.population);
+
+        self.period = None;
+        self.cycle_start = None;
+        self.recent_states = VecDeque::from([(hash_state(&self.bits), self.population_age, self.bits.clone())]);
+    }
+
+    // This is solely the logic for the Game of Life, generalised to any Life-like rule
+    pub fn update(&mut self) {
+        // This population has lived to see another day!
+        self.population_age += 1;
+
+        // Births and deaths need the previous generation, so only keep it around while recording
+        let previous = self.history.as_ref().map(|_| self.bits.clone());
 
-        for (cell, &state) in self.cells.iter_mut().zip(new_states.iter()) {
-            if state && !cell.state {
-                self.population += 1;
-            } else if !state && cell.state {
-                self.population -= 1;
-            }
+        self.bits.step(self.rule, self.boundary);
+        self.population = self.bits.population();
 
-            cell.state = state;
+        if self.period.is_none() {
+            self.detect_cycle();
         }
 
         // Calulate standard deviation
@@ -136,36 +158,54 @@ impl Grid {
         }
 
         self.final_population = self.population;
-    }
-
-    fn count_live_neighbors(&self, x: usize, y: usize) -> usize {
-        let mut live_neighbors = 0;
+        self.peak_population = self.peak_population.max(self.population);
 
-        for y_offset in -1..=1 {
-            for x_offset in -1..=1 {
-                if x_offset == 0 && y_offset == 0 {
-                    continue;
-                }
+        if let Some(history) = &mut self.history {
+            history.push(GenerationStats::new(self.population_age, &self.bits, previous.as_ref()));
+        }
+    }
 
-                let neighbor_x = x as i32 + x_offset;
-                let neighbor_y = y as i32 + y_offset;
+    // The episode is over once the population dies out, settles into a cycle or grows too old
+    pub fn is_finished(&self) -> bool {
+        self.termination().is_some()
+    }
 
-                if neighbor_x < 0 || neighbor_x >= self.columns as i32 {
-                    continue;
-                }
+    pub fn termination(&self) -> Option<Termination> {
+        if self.population == 0 {
+            Some(Termination::Extinct)
+        } else if let Some(period) = self.period {
+            Some(Termination::Cycle { period })
+        } else if self.population_age >= self.max_population_age {
+            Some(Termination::MaxAge)
+        } else {
+            None
+        }
+    }
 
-                if neighbor_y < 0 || neighbor_y >= self.rows as i32 {
-                    continue;
-                }
+    // Checks whether the current state exactly repeats one of the last max_cycle_length states
+    // Hashes are compared first and the full states only when they match
+    fn detect_cycle(&mut self) {
+        let hash = hash_state(&self.bits);
 
-                let neighbor_idx = neighbor_y as usize * self.columns + neighbor_x as usize;
+        let repeated = self.recent_states
+            .iter()
+            .find(|(recent_hash, _, recent)| *recent_hash == hash && *recent == self.bits)
+            .map(|&(_, generation, _)| generation);
 
-                if self.cells[neighbor_idx].state {
-                    live_neighbors += 1;
-                }
-            }
+        if let Some(generation) = repeated {
+            self.period = Some(self.population_age - generation);
+            self.cycle_start = Some(generation);
         }
 
-        live_neighbors
+        self.recent_states.push_back((hash, self.population_age, self.bits.clone()));
+        if self.recent_states.len() > self.max_cycle_length {
+            self.recent_states.pop_front();
+        }
     }
-}
\ No newline at end of file
+}
+
+fn hash_state(bits: &BitGrid) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bits.hash(&mut hasher);
+    hasher.finish()
+}
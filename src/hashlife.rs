@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+
+use bitvec::prelude::*;
+
+use crate::rule::Rule;
+
+type NodeId = u32;
+
+// The two leaves are always the first nodes in the arena
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+// Smallest root level, so that the root always has grandchildren to inspect
+const MIN_ROOT_LEVEL: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Quad {
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    level: u8,
+    quad: Quad,
+    population: u64,
+}
+
+// Inclusive bounds of the live cells, in grid coordinates
+// Cells which have travelled past the edges of the original grid give coordinates outside of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> u64 {
+        (self.max_x - self.min_x + 1) as u64
+    }
+
+    pub fn height(&self) -> u64 {
+        (self.max_y - self.min_y + 1) as u64
+    }
+
+    fn union(self, other: BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    fn offset(self, dx: i64, dy: i64) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x + dx,
+            min_y: self.min_y + dy,
+            max_x: self.max_x + dx,
+            max_y: self.max_y + dy,
+        }
+    }
+}
+
+// A memoized quadtree (HashLife) universe
+// Identical regions are stored once and the result of advancing each region is cached,
+// so repetitive patterns can be jumped ahead by exponentially many generations at a time
+// The universe is the unbounded plane, so the grid's boundary does not apply here
+pub struct HashLife {
+    rule: Rule,
+    nodes: Vec<Node>,
+    lookup: HashMap<Quad, NodeId>,
+    // Cached results of advancing a node by 2^j generations, keyed by (node, j)
+    results: HashMap<(NodeId, u8), NodeId>,
+    bounding_boxes: HashMap<NodeId, Option<BoundingBox>>,
+    empty: Vec<NodeId>,
+
+    // The root is always centered on the origin, with the grid's top left cell at (-x_offset, -y_offset)
+    root: NodeId,
+    initial_root: NodeId,
+    generation: u64,
+    x_offset: i64,
+    y_offset: i64,
+}
+
+impl HashLife {
+    // Builds a universe holding the grid state, or None if the rule births cells with no live neighbours
+    // since such rules turn the infinite empty background alive
+    pub fn new(columns: usize, rows: usize, grid_state: &BitVec, rule: Rule) -> Option<Self> {
+        if rule.next_state(false, 0) {
+            return None;
+        }
+
+        let leaf = |alive| Node { level: 0, quad: Quad { nw: DEAD, ne: DEAD, sw: DEAD, se: DEAD }, population: alive };
+
+        let mut life = HashLife {
+            rule,
+            nodes: vec![leaf(0), leaf(1)],
+            lookup: HashMap::new(),
+            results: HashMap::new(),
+            bounding_boxes: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            initial_root: DEAD,
+            generation: 0,
+            x_offset: (columns / 2) as i64,
+            y_offset: (rows / 2) as i64,
+        };
+
+        // Pick the smallest root which holds the whole grid around the origin
+        let mut level = MIN_ROOT_LEVEL;
+        while (1i64 << (level - 1)) < columns.max(rows) as i64 {
+            level += 1;
+        }
+
+        let half = 1i64 << (level - 1);
+        let live: Vec<(i64, i64)> = grid_state
+            .iter_ones()
+            .filter(|&idx| idx < columns * rows)
+            .map(|idx| ((idx % columns) as i64 - life.x_offset + half, (idx / columns) as i64 - life.y_offset + half))
+            .collect();
+
+        life.root = life.build(level, 0, 0, &live);
+        life.initial_root = life.root;
+
+        Some(life)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    pub fn bounding_box(&mut self) -> Option<BoundingBox> {
+        let half = 1i64 << (self.level(self.root) - 1);
+        let root = self.root;
+        self.node_bounding_box(root).map(|bbox| bbox.offset(self.x_offset - half, self.y_offset - half))
+    }
+
+    // Coordinates of every live cell at the current generation, in the same grid coordinates as the bounding box
+    pub fn live_cells(&self) -> Vec<(i64, i64)> {
+        let half = 1i64 << (self.level(self.root) - 1);
+        let mut cells = Vec::with_capacity(self.population() as usize);
+        self.collect_cells(self.root, self.x_offset - half, self.y_offset - half, &mut cells);
+
+        cells
+    }
+
+    // Advances the universe by the given number of generations
+    pub fn advance(&mut self, generations: u64) {
+        self.root = self.advance_root(self.root, generations);
+        self.generation += generations;
+    }
+
+    // Moves the universe to the given generation, replaying from the initial state if it lies in the past
+    // Replays are cheap since every region seen before is already cached
+    pub fn seek(&mut self, generation: u64) {
+        if generation < self.generation {
+            self.root = self.initial_root;
+            self.generation = 0;
+        }
+
+        self.advance(generation - self.generation);
+    }
+
+    pub fn population_at(&mut self, generation: u64) -> u64 {
+        self.seek(generation);
+        self.population()
+    }
+
+    pub fn bounding_box_at(&mut self, generation: u64) -> Option<BoundingBox> {
+        self.seek(generation);
+        self.bounding_box()
+    }
+
+    // Returns the smallest period p <= max_period such that the pattern at the current generation
+    // reappears unchanged, in the same place, p generations later
+    pub fn period(&mut self, max_period: usize) -> Option<usize> {
+        let start = self.root;
+        let mut current = start;
+
+        for period in 1..=max_period {
+            current = self.advance_root(current, 1);
+            if self.same_state(start, current) {
+                return Some(period);
+            }
+        }
+
+        None
+    }
+
+    fn level(&self, node: NodeId) -> u8 {
+        self.nodes[node as usize].level
+    }
+
+    fn quad(&self, node: NodeId) -> Quad {
+        self.nodes[node as usize].quad
+    }
+
+    fn population_of(&self, node: NodeId) -> u64 {
+        self.nodes[node as usize].population
+    }
+
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        let quad = Quad { nw, ne, sw, se };
+        if let Some(&id) = self.lookup.get(&quad) {
+            return id;
+        }
+
+        let population = [nw, ne, sw, se].iter().map(|&n| self.population_of(n)).sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node { level: self.level(nw) + 1, quad, population });
+        self.lookup.insert(quad, id);
+
+        id
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let next = self.join(e, e, e, e);
+            self.empty.push(next);
+        }
+
+        self.empty[level as usize]
+    }
+
+    // Builds the node of the given level covering [x, x + 2^level) x [y, y + 2^level) from a list of live cells
+    fn build(&mut self, level: u8, x: i64, y: i64, live: &[(i64, i64)]) -> NodeId {
+        if live.is_empty() {
+            return self.empty(level);
+        }
+
+        if level == 0 {
+            return ALIVE;
+        }
+
+        let half = 1i64 << (level - 1);
+        let in_quadrant = |qx: i64, qy: i64| -> Vec<(i64, i64)> {
+            live.iter()
+                .copied()
+                .filter(|&(cx, cy)| (cx >= qx && cx < qx + half) && (cy >= qy && cy < qy + half))
+                .collect()
+        };
+
+        let (nw_cells, ne_cells) = (in_quadrant(x, y), in_quadrant(x + half, y));
+        let (sw_cells, se_cells) = (in_quadrant(x, y + half), in_quadrant(x + half, y + half));
+
+        let nw = self.build(level - 1, x, y, &nw_cells);
+        let ne = self.build(level - 1, x + half, y, &ne_cells);
+        let sw = self.build(level - 1, x, y + half, &sw_cells);
+        let se = self.build(level - 1, x + half, y + half, &se_cells);
+
+        self.join(nw, ne, sw, se)
+    }
+
+    // Surrounds the node with empty space, doubling its size while keeping it centered
+    fn expand(&mut self, node: NodeId) -> NodeId {
+        let q = self.quad(node);
+        let e = self.empty(self.level(node) - 1);
+
+        let nw = self.join(e, e, e, q.nw);
+        let ne = self.join(e, e, q.ne, e);
+        let sw = self.join(e, q.sw, e, e);
+        let se = self.join(q.se, e, e, e);
+
+        self.join(nw, ne, sw, se)
+    }
+
+    // The node one level down covering the center of this node
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let q = self.quad(node);
+        let (nw, ne, sw, se) = (self.quad(q.nw), self.quad(q.ne), self.quad(q.sw), self.quad(q.se));
+
+        self.join(nw.se, ne.sw, sw.ne, se.nw)
+    }
+
+    // Whether every live cell lies in the central quarter of the node
+    // Patterns grow by at most one cell per generation, which then leaves room to advance
+    // by up to a quarter of the node's size without anything falling off the result
+    fn is_padded(&self, node: NodeId) -> bool {
+        let q = self.quad(node);
+        let (nw, ne, sw, se) = (self.quad(q.nw), self.quad(q.ne), self.quad(q.sw), self.quad(q.se));
+        let (nw, ne, sw, se) = (self.quad(nw.se), self.quad(ne.sw), self.quad(sw.ne), self.quad(se.nw));
+
+        let inner = self.population_of(nw.se) + self.population_of(ne.sw) + self.population_of(sw.ne) + self.population_of(se.nw);
+        inner == self.population_of(node)
+    }
+
+    fn advance_root(&mut self, mut root: NodeId, generations: u64) -> NodeId {
+        for j in 0..u64::BITS as u8 {
+            if generations >> j == 0 {
+                break;
+            }
+
+            if generations & (1 << j) == 0 {
+                continue;
+            }
+
+            while self.level(root) < (j + 3).max(MIN_ROOT_LEVEL) || !self.is_padded(root) {
+                root = self.expand(root);
+            }
+
+            root = self.successor(root, j);
+        }
+
+        root
+    }
+
+    // Advances a node of level L by 2^j generations (j <= L - 2), returning its center at level L - 1
+    fn successor(&mut self, node: NodeId, j: u8) -> NodeId {
+        let level = self.level(node);
+
+        if self.population_of(node) == 0 {
+            return self.empty(level - 1);
+        }
+
+        if let Some(&result) = self.results.get(&(node, j)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.step_base(node)
+        } else {
+            let q = self.quad(node);
+            let (nw, ne, sw, se) = (self.quad(q.nw), self.quad(q.ne), self.quad(q.sw), self.quad(q.se));
+
+            // The nine overlapping sub-nodes one level down
+            let n01 = self.join(nw.ne, ne.nw, nw.se, ne.sw);
+            let n10 = self.join(nw.sw, nw.se, sw.nw, sw.ne);
+            let n11 = self.join(nw.se, ne.sw, sw.ne, se.nw);
+            let n12 = self.join(ne.sw, ne.se, se.nw, se.ne);
+            let n21 = self.join(sw.ne, se.nw, sw.se, se.sw);
+            let subnodes = [q.nw, n01, q.ne, n10, n11, n12, q.sw, n21, q.se];
+
+            // At full speed every sub-node is advanced by half the jump, otherwise only their centers are taken
+            let full_speed = j == level - 2;
+            let mut m = [DEAD; 9];
+            for (i, &n) in subnodes.iter().enumerate() {
+                m[i] = if full_speed { self.successor(n, level - 3) } else { self.center(n) };
+            }
+
+            let a = self.join(m[0], m[1], m[3], m[4]);
+            let b = self.join(m[1], m[2], m[4], m[5]);
+            let c = self.join(m[3], m[4], m[6], m[7]);
+            let d = self.join(m[4], m[5], m[7], m[8]);
+
+            let next_j = if full_speed { level - 3 } else { j };
+            let ra = self.successor(a, next_j);
+            let rb = self.successor(b, next_j);
+            let rc = self.successor(c, next_j);
+            let rd = self.successor(d, next_j);
+
+            self.join(ra, rb, rc, rd)
+        };
+
+        self.results.insert((node, j), result);
+        result
+    }
+
+    // Advances a 4x4 node by a single generation, returning its central 2x2 cells
+    fn step_base(&mut self, node: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        let q = self.quad(node);
+
+        for (child, (cx, cy)) in [(q.nw, (0, 0)), (q.ne, (2, 0)), (q.sw, (0, 2)), (q.se, (2, 2))] {
+            let leaves = self.quad(child);
+            cells[cy][cx] = leaves.nw == ALIVE;
+            cells[cy][cx + 1] = leaves.ne == ALIVE;
+            cells[cy + 1][cx] = leaves.sw == ALIVE;
+            cells[cy + 1][cx + 1] = leaves.se == ALIVE;
+        }
+
+        let mut next = [DEAD; 4];
+        for (i, (x, y)) in [(1, 1), (2, 1), (1, 2), (2, 2)].into_iter().enumerate() {
+            let live_neighbors = cells[y - 1..=y + 1]
+                .iter()
+                .flat_map(|row| &row[x - 1..=x + 1])
+                .filter(|&&alive| alive)
+                .count() - cells[y][x] as usize;
+
+            if self.rule.next_state(cells[y][x], live_neighbors) {
+                next[i] = ALIVE;
+            }
+        }
+
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    // Compares two centered roots, which may be of different levels
+    fn same_state(&mut self, mut a: NodeId, mut b: NodeId) -> bool {
+        while self.level(a) < self.level(b) {
+            a = self.expand(a);
+        }
+        while self.level(b) < self.level(a) {
+            b = self.expand(b);
+        }
+
+        a == b
+    }
+
+    fn collect_cells(&self, node: NodeId, x: i64, y: i64, cells: &mut Vec<(i64, i64)>) {
+        if self.population_of(node) == 0 {
+            return;
+        }
+
+        let level = self.level(node);
+        if level == 0 {
+            cells.push((x, y));
+            return;
+        }
+
+        let half = 1i64 << (level - 1);
+        let q = self.quad(node);
+        for (child, dx, dy) in [(q.nw, 0, 0), (q.ne, half, 0), (q.sw, 0, half), (q.se, half, half)] {
+            self.collect_cells(child, x + dx, y + dy, cells);
+        }
+    }
+
+    // Bounding box of the live cells in the node's own coordinates
+    fn node_bounding_box(&mut self, node: NodeId) -> Option<BoundingBox> {
+        if self.population_of(node) == 0 {
+            return None;
+        }
+
+        let level = self.level(node);
+        if level == 0 {
+            return Some(BoundingBox { min_x: 0, min_y: 0, max_x: 0, max_y: 0 });
+        }
+
+        if let Some(&bbox) = self.bounding_boxes.get(&node) {
+            return bbox;
+        }
+
+        let half = 1i64 << (level - 1);
+        let q = self.quad(node);
+        let bbox = [(q.nw, 0, 0), (q.ne, half, 0), (q.sw, 0, half), (q.se, half, half)]
+            .into_iter()
+            .filter_map(|(child, dx, dy)| self.node_bounding_box(child).map(|bbox| bbox.offset(dx, dy)))
+            .reduce(BoundingBox::union);
+
+        self.bounding_boxes.insert(node, bbox);
+        bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    use crate::bitgrid::BitGrid;
+    use crate::boundary::Boundary;
+
+    // Big enough that nothing placed in the middle reaches the edges within 100 generations, where the dead boundary
+    // of the grid and the unbounded plane of HashLife would part ways
+    const SIZE: usize = 220;
+
+    fn centered(cells: &[(usize, usize)]) -> BitVec {
+        let mut state = bitvec![0; SIZE * SIZE];
+        for &(x, y) in cells {
+            state.set((y + SIZE / 2) * SIZE + x + SIZE / 2, true);
+        }
+        state
+    }
+
+    fn sorted(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        cells.sort_unstable();
+        cells
+    }
+
+    fn grid_cells(grid: &BitGrid) -> Vec<(i64, i64)> {
+        sorted(grid.live_cells().map(|(x, y)| (x as i64, y as i64)).collect())
+    }
+
+    #[test]
+    fn agrees_with_the_grid_engine() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let glider = centered(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        let r_pentomino = centered(&[(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)]);
+        let soup = centered(&(0..100).filter(|_| rng.gen_bool(0.4)).map(|i| (i % 10, i / 10)).collect::<Vec<_>>());
+
+        for rule in ["B3/S23", "B36/S23"].map(|rule| rule.parse::<Rule>().unwrap()) {
+            for state in [&glider, &r_pentomino, &soup] {
+                let mut grid = BitGrid::new(SIZE, SIZE, state);
+                let mut life = HashLife::new(SIZE, SIZE, state, rule).expect("Expected a rule without B0");
+                let mut history = vec![grid_cells(&grid)];
+
+                for generation in 1..=100 {
+                    grid.step(rule, Boundary::Dead);
+                    life.advance(1);
+                    history.push(grid_cells(&grid));
+
+                    assert_eq!(life.generation(), generation);
+                    assert_eq!(sorted(life.live_cells()), history[generation as usize], "{} at generation {}", rule, generation);
+                }
+
+                // Going back replays from the start, and jumping ahead takes many generations at once
+                for generation in [37, 0, 100, 1, 64] {
+                    life.seek(generation);
+                    assert_eq!(sorted(life.live_cells()), history[generation as usize], "{} seeking generation {}", rule, generation);
+                    assert_eq!(life.population_at(generation), history[generation as usize].len() as u64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_rules_which_birth_on_zero_neighbours() {
+        let state = centered(&[(0, 0), (1, 0), (2, 0)]);
+
+        for rule in ["B0/S23", "B0123478/S34678"] {
+            assert!(HashLife::new(SIZE, SIZE, &state, rule.parse().unwrap()).is_none(), "{}", rule);
+        }
+        assert!(HashLife::new(SIZE, SIZE, &state, Rule::conway()).is_some());
+    }
+}
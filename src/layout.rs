@@ -0,0 +1,40 @@
+use nannou::prelude::*;
+
+// Maps the cells of a grid onto the window, stretching them to fill it
+pub struct Layout {
+    pub cell_width: f32,
+    pub cell_height: f32,
+    window_rect: Rect,
+    columns: usize,
+    rows: usize,
+}
+
+impl Layout {
+    pub fn new(window_rect: Rect, columns: usize, rows: usize) -> Self {
+        Layout {
+            cell_width: window_rect.w() / columns as f32,
+            cell_height: window_rect.h() / rows as f32,
+            window_rect,
+            columns,
+            rows,
+        }
+    }
+
+    // The area the whole grid is drawn in, with the first row at the top of the window
+    // so patterns appear the same way up as in their files
+    pub fn rect(&self) -> Rect {
+        self.window_rect
+    }
+
+    // The cell under a point in window coordinates, if there is one
+    pub fn cell_at(&self, point: Point2) -> Option<(usize, usize)> {
+        let x = ((point.x - self.window_rect.left()) / self.cell_width).floor();
+        let y = ((self.window_rect.top() - point.y) / self.cell_height).floor();
+
+        if x < 0.0 || y < 0.0 || x >= self.columns as f32 || y >= self.rows as f32 {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+}
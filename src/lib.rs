@@ -0,0 +1,20 @@
+pub mod grid;
+pub mod agent;
+pub mod env;
+pub mod ga;
+pub mod qlearning;
+pub mod rule;
+pub mod boundary;
+pub mod bitgrid;
+pub mod hashlife;
+pub mod census;
+pub mod stats;
+pub mod fitness;
+pub mod checkpoint;
+pub mod metrics;
+pub mod config;
+pub mod pattern;
+pub mod rle;
+pub mod plaintext;
+pub mod lif;
+pub mod constants;
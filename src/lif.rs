@@ -0,0 +1,255 @@
+use std::fs;
+use std::path::Path;
+
+use crate::rule::Rule;
+use crate::pattern::{self, Pattern, PatternError};
+
+// Reads a pattern in either of the Life 1.05 or Life 1.06 (.lif, .life) formats
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Pattern, PatternError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+// The two versions of the format, which share the .lif and .life extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LifVersion {
+    // Blocks of cells drawn as text, with room for comments and the rule
+    V105,
+    // A list of live cells, which every reader of the format understands
+    #[default]
+    V106,
+}
+
+pub fn write_file<P: AsRef<Path>>(path: P, pattern: &Pattern, version: LifVersion) -> Result<(), PatternError> {
+    fs::write(path, write(pattern, version))?;
+    Ok(())
+}
+
+pub fn write(pattern: &Pattern, version: LifVersion) -> String {
+    match version {
+        LifVersion::V105 => write_105(pattern),
+        LifVersion::V106 => write_106(pattern),
+    }
+}
+
+// The version is given by the "#Life 1.0x" line at the top of the file
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let header = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("");
+
+    match header {
+        "#Life 1.06" => parse_106(text),
+        "#Life 1.05" => parse_105(text),
+        _ => Err(PatternError::Parse { line: 1, message: "expected a \"#Life 1.05\" or \"#Life 1.06\" header".to_string() }),
+    }
+}
+
+// Every line after the header holds the x and y coordinates of one live cell
+fn parse_106(text: &str) -> Result<Pattern, PatternError> {
+    let mut rule = None;
+    let mut comments = Vec::new();
+    let mut live: Vec<(i64, i64)> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("#Life") {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            // Not part of the format, but some programs keep descriptions and rules the Life 1.05 way
+            match comment.chars().next() {
+                Some('D') | Some('C') => comments.push(comment[1..].trim().to_string()),
+                Some('R') => rule = Some(comment[1..].trim().parse()?),
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut coordinates = line.split_whitespace().map(|value| {
+            value.parse::<i64>().map_err(|_| PatternError::Parse { line: line_number, message: format!("invalid coordinate \"{}\"", value) })
+        });
+
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(x), Some(y), None) => live.push((x?, y?)),
+            _ => return Err(PatternError::Parse { line: line_number, message: "expected \"x y\"".to_string() }),
+        }
+    }
+
+    let mut pattern = from_cells(&live).map_err(|message| PatternError::Parse { line: text.lines().count(), message })?;
+    pattern.rule = rule;
+    pattern.comments = comments;
+
+    Ok(pattern)
+}
+
+// Blocks of '.' and '*' rows, each starting with a "#P x y" line giving the position of its top left cell
+fn parse_105(text: &str) -> Result<Pattern, PatternError> {
+    let mut rule = None;
+    let mut comments = Vec::new();
+    let mut live: Vec<(i64, i64)> = Vec::new();
+    let (mut block_x, mut y) = (0, 0);
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("#Life") {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let (kind, value) = comment.split_at(comment.chars().next().map_or(0, char::len_utf8));
+            match kind {
+                "D" | "C" => comments.push(value.trim().to_string()),
+                "N" => rule = Some(Rule::conway()),
+                // The rule is written survival first, e.g. "#R 23/3"
+                "R" => rule = Some(value.trim().parse()?),
+                "P" => {
+                    let mut position = value.split_whitespace().map(|value| value.parse::<i64>());
+                    match (position.next(), position.next()) {
+                        (Some(Ok(x)), Some(Ok(py))) => {
+                            block_x = x;
+                            y = py;
+                        }
+                        _ => return Err(PatternError::Parse { line: line_number, message: "expected \"#P x y\"".to_string() }),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let too_far = || PatternError::Parse { line: line_number, message: "cell position too large".to_string() };
+        for (dx, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                '*' => live.push((block_x.checked_add(dx as i64).ok_or_else(too_far)?, y)),
+                c => return Err(PatternError::Parse { line: line_number, message: format!("unexpected character '{}'", c) }),
+            }
+        }
+        y = y.checked_add(1).ok_or_else(too_far)?;
+    }
+
+    let mut pattern = from_cells(&live).map_err(|message| PatternError::Parse { line: text.lines().count(), message })?;
+    pattern.rule = rule;
+    pattern.comments = comments;
+
+    Ok(pattern)
+}
+
+// Crops a set of cells on the unbounded plane down to their bounding box, unless it is too large to hold
+fn from_cells(live: &[(i64, i64)]) -> Result<Pattern, String> {
+    if live.is_empty() {
+        return Ok(Pattern::new(0, 0));
+    }
+
+    let min_x = live.iter().map(|&(x, _)| x).min().unwrap();
+    let max_x = live.iter().map(|&(x, _)| x).max().unwrap();
+    let min_y = live.iter().map(|&(_, y)| y).min().unwrap();
+    let max_y = live.iter().map(|&(_, y)| y).max().unwrap();
+
+    // Cells at opposite ends of the i64 range are further apart than an i64 can count
+    let extent = |min: i64, max: i64| max.checked_sub(min).and_then(|span| usize::try_from(span).ok()).and_then(|span| span.checked_add(1));
+    let (width, height) = match (extent(min_x, max_x), extent(min_y, max_y)) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err(format!("cells from ({}, {}) to ({}, {}) span more than the largest pattern supported", min_x, min_y, max_x, max_y)),
+    };
+    pattern::check_size(width, height)?;
+
+    let mut pattern = Pattern::new(width, height);
+    for &(x, y) in live {
+        pattern.set((x - min_x) as usize, (y - min_y) as usize, true);
+    }
+
+    Ok(pattern)
+}
+
+// Life 1.06 has no place for comments or a rule, so only the live cells are written
+fn write_106(pattern: &Pattern) -> String {
+    let mut text = String::from("#Life 1.06\n");
+
+    for idx in pattern.cells.iter_ones() {
+        text.push_str(&format!("{} {}\n", idx % pattern.width, idx / pattern.width));
+    }
+
+    text
+}
+
+// Life 1.05 cannot say there is no rule, so a pattern without one is written as Conway's Life
+fn write_105(pattern: &Pattern) -> String {
+    let mut text = String::from("#Life 1.05\n");
+
+    for comment in &pattern.comments {
+        text.push_str(&format!("#D {}\n", comment));
+    }
+
+    match pattern.rule {
+        Some(rule) if rule != Rule::conway() => {
+            let digits = |on: &dyn Fn(usize) -> bool| (0..=8).filter(|&n| on(n)).map(|n| n.to_string()).collect::<String>();
+            text.push_str(&format!("#R {}/{}\n", digits(&|n| rule.survives_on(n)), digits(&|n| rule.births_on(n))));
+        }
+        _ => text.push_str("#N\n"),
+    }
+
+    text.push_str("#P 0 0\n");
+    for y in 0..pattern.height {
+        let row: String = (0..pattern.width).map(|x| if pattern.get(x, y) { '*' } else { '.' }).collect();
+        text.push_str(row.trim_end_matches('.'));
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Both versions crop to the live cells, so the pattern has one on every edge
+    fn pattern() -> Pattern {
+        let mut pattern = Pattern::new(6, 4);
+        for (x, y) in [(2, 0), (0, 1), (3, 1), (5, 2), (1, 3), (2, 3)] {
+            pattern.set(x, y, true);
+        }
+        pattern
+    }
+
+    #[test]
+    fn life_106_round_trips_the_cells() {
+        let pattern = pattern();
+        assert_eq!(parse(&write(&pattern, LifVersion::V106)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn life_105_round_trips_the_cells_comments_and_rule() {
+        let mut pattern = pattern();
+        pattern.comments = vec!["A test pattern".to_string()];
+
+        for rule in ["B3/S23", "B36/S23", "B2/S"] {
+            pattern.rule = Some(rule.parse().unwrap());
+            assert_eq!(parse(&write(&pattern, LifVersion::V105)).unwrap(), pattern, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn reads_negative_coordinates() {
+        let pattern = parse("#Life 1.06\n-1 -1\n0 -1\n1 1\n").unwrap();
+
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells.iter_ones().collect::<Vec<_>>(), vec![0, 1, 8]);
+    }
+
+    #[test]
+    fn rejects_cells_too_far_apart_to_hold() {
+        for text in [
+            "#Life 1.06\n0 0\n3000000000 3000000000\n".to_string(),
+            format!("#Life 1.06\n{} 0\n{} 0\n", i64::MIN, i64::MAX),
+            format!("#Life 1.06\n0 {}\n0 {}\n", i64::MAX, i64::MIN),
+            "#Life 1.05\n#P 0 0\n*\n#P 100000000 100000000\n*\n".to_string(),
+            format!("#Life 1.05\n#P {} {}\n..*\n*\n", i64::MAX, i64::MAX),
+        ] {
+            assert!(matches!(parse(&text), Err(PatternError::Parse { .. })), "{:?}", text);
+        }
+    }
+}
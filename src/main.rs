@@ -1,145 +1,337 @@
-use std::cmp::min;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use rand::Rng;
 use nannou::prelude::*;
-
-mod grid;
-mod cell;
-mod agent;
-mod ga;
-mod constants;
-
-use crate::grid::Grid;
-use crate::agent::Agent;
-use crate::constants::{WINDOW_WIDTH_MAX, WINDOW_HEIGHT_MAX, EPSILON, MAX_POPULATION_REPEATS, MAX_POPULATION_AGE};
+use bitvec::prelude::{bitvec, BitVec};
+
+mod layout;
+mod clock;
+mod trainer;
+mod render;
+
+use crate::layout::Layout;
+use crate::clock::Clock;
+use crate::render::Renderer;
+use crate::trainer::{save_effective_config, Command, Progress, Report, Trainer};
+use game_of_life::grid::Grid;
+use game_of_life::agent::Agent;
+use game_of_life::config::Config;
+use game_of_life::checkpoint::Checkpoint;
+use game_of_life::metrics::MetricsLog;
+use game_of_life::pattern;
+use game_of_life::constants::CONFIG_PATH;
 
 
 struct Model {
-    agent: Agent,
+    // The agent trains on a thread of its own, and only reports back how it is doing
+    trainer: Trainer,
+    // The settings of the agent, which the grid is made with
+    config: Config,
     grid: Grid,
 
-    // To track the number of times the population size has repeated
-    population_repeats: usize,
-    last_cycle_average: f32,
-    cycle_average_repeats: usize,
-
-    // Counter to track the total number of iterations
-    iterations: usize,
+    // Where each cell of the grid is drawn in the window, and what draws them
+    layout: Layout,
+    renderer: Renderer,
+
+    // How many generations to run each frame, and whether the simulation is paused
+    clock: Clock,
+
+    // Once the user starts drawing, the grid stands still until the drawing is submitted
+    editing: bool,
+    // Whether held mouse buttons paint cells alive or erase them
+    brush: Option<bool>,
+
+    // The state the grid started from and its value, once it is known, and how the episode before this one ended
+    seed: BitVec,
+    seed_value: Option<f32>,
+    previous_ending: Option<String>,
+
+    // The best seed the agent has found so far, which is played whenever the grid finishes
+    best: BitVec,
+    best_value: Option<f32>,
+    // How the agent stood after its last round of training
+    progress: Option<Progress>,
+    // Whether the trainer waits for rounds to be stepped through one at a time, and whether the grid moves on to the
+    // best seed once the round being stepped through is done
+    training_paused: bool,
+    step_pending: bool,
+
+    // Whether the statistics are drawn over the grid
+    show_hud: bool,
 }
 
+const USAGE: &str = "Usage: game_of_life [--config PATH] [--set KEY=VALUE]... [PATTERN]...
+
+Patterns (.rle, .cells, .lif) are added to the state space before training starts.
+Settings are read from config.toml when it exists, and --set takes precedence over them.";
+
+const CONTROLS: &str = "Controls:
+    Left drag     Paint cells, which stops the grid so it can be drawn on
+    Right drag    Erase cells
+    Backspace     Clear the grid to draw from scratch
+    Enter         Submit the drawing to the agent to be evaluated, and play it
+    Space         Pause or resume
+    N             Step one generation
+    E             Step one training episode, pausing training, then skip to the best seed
+    T             Pause or resume training
+    H             Show or hide the statistics
+    G             Show or hide the outlines of the cells
+    Up / Down     Double or halve the speed
+    F             Switch between generations per frame and a fixed number of generations per second
+    R             Throw away everything the agent has learned and start over
+    S             Save the best seed
+    C             Save a checkpoint";
+
 fn model(app: &App) -> Model {
-    let num_cells = get_num_cells(WINDOW_WIDTH_MAX, WINDOW_HEIGHT_MAX);
-    let mut agent = Agent::new(EPSILON, num_cells);
+    let (config, patterns) = load_config();
+
+    let resumed = load_checkpoint(&config);
+    let resuming = resumed.is_some();
+    let mut agent = resumed.unwrap_or_else(|| Agent::new(&config, Box::new(config.agent.fitness)));
+    save_effective_config(&agent.config);
+
+    // Seed the search with any patterns given on the command line
+    pattern::import_into(&mut agent, &patterns);
 
     // Initialize grid with new state from agent
     let grid_state = agent.get_new_state();
+    let config = agent.config.clone();
 
-    let grid = Grid::new(WINDOW_WIDTH_MAX, WINDOW_HEIGHT_MAX, &grid_state);
+    let mut grid = Grid::new(&config.grid, &grid_state);
+    grid.record_history();
 
     app.new_window()
-        .size(WINDOW_WIDTH_MAX as u32, WINDOW_HEIGHT_MAX as u32)
+        .size(config.window.width as u32, config.window.height as u32)
         .resizable(true)
         .view(view)
         .event(window_event)
         .build()
         .unwrap();
 
-    Model { grid, agent, population_repeats: 0, last_cycle_average: 0.0, cycle_average_repeats: 0, iterations: 0 }
+    let layout = Layout::new(app.window_rect(), config.grid.columns, config.grid.rows);
+    let mut renderer = Renderer::new(&app.main_window(), config.grid.columns, config.grid.rows, config.window.grid_lines)
+        .unwrap_or_else(|e| fail(&e));
+    renderer.update(&app.main_window(), &grid);
+
+    // A resumed run carries on the log of the run it was saved from, instead of starting it over
+    let metrics = if resuming {
+        MetricsLog::append(&config.files.metrics, agent.episodes)
+    } else {
+        MetricsLog::create(&config.files.metrics)
+    };
+    let metrics = match metrics {
+        Ok(metrics) => Some(metrics),
+        Err(e) => {
+            eprintln!("Could not create {}, training will not be recorded: {}", config.files.metrics, e);
+            None
+        }
+    };
+
+    println!("{}", CONTROLS);
+
+    let clock = Clock::new(&config.window);
+    let show_hud = config.window.hud;
+
+    Model {
+        trainer: Trainer::spawn(agent, metrics),
+        config,
+        grid,
+        layout,
+        renderer,
+        clock,
+        editing: false,
+        brush: None,
+        seed: grid_state.clone(),
+        seed_value: None,
+        previous_ending: None,
+        best: grid_state,
+        best_value: None,
+        progress: None,
+        training_paused: false,
+        step_pending: false,
+        show_hud,
+    }
 }
 
-// TODO: Implement centralized reset function which can be called from window_event
 fn window_event(app: &App, model: &mut Model, event: WindowEvent) {
-    // Trigger new grid if window is resized or if mouse is clicked
     match event {
+        // The grid's size does not depend on the window, so a resize only moves the cells on screen
         WindowEvent::Resized(_new_size) => {
-            let new_rect = app.window_rect();
-            let w = min(new_rect.w() as usize, WINDOW_WIDTH_MAX as usize);
-            let h = min(new_rect.h() as usize, WINDOW_HEIGHT_MAX as usize);
-
-            // Reset the agent
-            model.agent = Agent::new(EPSILON, get_num_cells(w as f32, h as f32));
-
-            // Reset the grid and initialize it to a new state from the agent
-            let grid_state = model.agent.get_new_state();
-
-            // Reset the number of iterations
-            model.iterations = 0;
-
-            model.grid = Grid::new(w as f32, h as f32, &grid_state);
+            model.layout = Layout::new(app.window_rect(), model.grid.columns, model.grid.rows);
         }
-        WindowEvent::MousePressed(_button) => {
-            let new_rect = app.window_rect();
-            let w = min(new_rect.w() as usize, WINDOW_WIDTH_MAX as usize);
-            let h = min(new_rect.h() as usize, WINDOW_HEIGHT_MAX as usize);
-
-            // Reset the agent
-            model.agent = Agent::new(EPSILON, get_num_cells(w as f32, h as f32));
-
-            // Reset the grid and initialize it to a new state from the agent
-            let grid_state = model.agent.get_new_state();
-
-            // Reset the number of iterations
-            model.iterations = 0;
-
-            model.grid = Grid::new(w as f32, h as f32, &grid_state);
+        WindowEvent::MousePressed(button) => {
+            model.brush = match button {
+                MouseButton::Left => Some(true),
+                MouseButton::Right => Some(false),
+                _ => None,
+            };
+            paint(model, app.mouse.position());
+        }
+        WindowEvent::MouseMoved(point) => {
+            paint(model, point);
+        }
+        WindowEvent::MouseReleased(_button) => {
+            model.brush = None;
+        }
+        WindowEvent::KeyPressed(Key::Back) | WindowEvent::KeyPressed(Key::Delete) => {
+            let empty = bitvec![0; model.grid.columns * model.grid.rows];
+            model.grid = Grid::new(&model.config.grid, &empty);
+            model.editing = true;
+        }
+        WindowEvent::KeyPressed(Key::Return) => {
+            submit_drawing(model);
+        }
+        WindowEvent::KeyPressed(Key::Space) => {
+            model.clock.toggle_pause();
+            println!("Speed: {}", model.clock);
+        }
+        WindowEvent::KeyPressed(Key::N) => {
+            model.clock.paused = true;
+            advance(model);
+        }
+        WindowEvent::KeyPressed(Key::E) => {
+            model.trainer.send(Command::Step);
+            model.training_paused = true;
+            model.step_pending = true;
+        }
+        WindowEvent::KeyPressed(Key::T) => {
+            model.trainer.send(Command::TogglePause);
+            model.training_paused = !model.training_paused;
+            println!("Training {}", if model.training_paused { "paused" } else { "resumed" });
+        }
+        WindowEvent::KeyPressed(Key::H) => {
+            model.show_hud = !model.show_hud;
+        }
+        WindowEvent::KeyPressed(Key::G) => {
+            model.renderer.grid_lines = !model.renderer.grid_lines;
+        }
+        WindowEvent::KeyPressed(Key::Up) => {
+            model.clock.faster();
+            println!("Speed: {}", model.clock);
+        }
+        WindowEvent::KeyPressed(Key::Down) => {
+            model.clock.slower();
+            println!("Speed: {}", model.clock);
+        }
+        WindowEvent::KeyPressed(Key::F) => {
+            model.clock.toggle_fixed_rate();
+            println!("Speed: {}", model.clock);
+        }
+        WindowEvent::KeyPressed(Key::R) => {
+            model.trainer.send(Command::Reset);
+        }
+        WindowEvent::KeyPressed(Key::S) => {
+            model.trainer.send(Command::SaveBestSeed);
+        }
+        WindowEvent::KeyPressed(Key::C) => {
+            model.trainer.send(Command::SaveCheckpoint);
         }
         _ => {}
     }
 }
 
-fn update(app: &App, model: &mut Model, _update: Update) {
-    // Increment the number of iterations
-    model.iterations += 1;
+fn update(app: &App, model: &mut Model, update: Update) {
+    receive_reports(model);
 
-    // Print data about the model and agent every 1000 iterations
-    if model.iterations % 700 == 0 {
-        println!("Epsilon: {}", model.agent.epsilon);
-        println!("State Space Size: {}", model.agent.state_space.len());
-        println!("Average Value: {}", model.agent.previous_avg_value);
-        println!("-------------------------");
+    // Hold the grid still while it is being drawn on
+    if !model.editing {
+        for _ in 0..model.clock.ticks(update.since_last) {
+            advance(model);
+        }
     }
 
-    // Check if the population size has repeated cyclically
-    if model.grid.cycle_average == model.last_cycle_average {
-        model.cycle_average_repeats += 1;
-    } else {
-        model.last_cycle_average = model.grid.cycle_average;
-        model.cycle_average_repeats = 0;
+    // Whatever changed the grid since the last frame, stepping it or drawing on it, shows in the next one
+    model.renderer.update(&app.main_window(), &model.grid);
+}
+
+// Catches up with everything the trainer reported since the last frame
+fn receive_reports(model: &mut Model) {
+    let reports: Vec<Report> = model.trainer.reports().collect();
+
+    for report in reports {
+        match report {
+            Report::Progress(progress) => {
+                model.progress = Some(progress);
+
+                // The round stepped through with E is done, and whatever it found is the best seed by now
+                if model.step_pending {
+                    model.step_pending = false;
+                    finish_episode(model);
+                }
+            }
+            Report::Best { state, value } => {
+                if state == model.seed {
+                    model.seed_value = Some(value);
+                }
+                model.best = state;
+                model.best_value = Some(value);
+            }
+            Report::Evaluated { state, value } => {
+                if state == model.seed {
+                    model.seed_value = value;
+                }
+            }
+            Report::Reset(state) => {
+                model.best = state.clone();
+                model.best_value = None;
+                model.progress = None;
+                model.previous_ending = None;
+                start_grid(model, state, None);
+            }
+        }
     }
+}
 
-    // Trigger new grid if population is zero or if the population size continues to repeat or if the population age is too high
-    if model.grid.population == 0 || model.cycle_average_repeats >= MAX_POPULATION_REPEATS || model.grid.population_age >= MAX_POPULATION_AGE{
-        let new_rect = app.window_rect();
-        let w = min(new_rect.w() as usize, WINDOW_WIDTH_MAX as usize);
-        let h = min(new_rect.h() as usize, WINDOW_HEIGHT_MAX as usize);
+// Runs one generation of the grid, or once it has finished, moves on to the best seed
+fn advance(model: &mut Model) {
+    // Trigger new grid if population is zero or if the population has settled into a cycle or if the population age is too high
+    if model.grid.is_finished() {
+        finish_episode(model);
+    } else {
+        // Update the grid, which also increases the population age
+        model.grid.update();
+    }
+}
 
-        // Reset population repeat counter
-        model.population_repeats = 0;
+// Replays the best seed found so far, while training carries on in the background
+fn finish_episode(model: &mut Model) {
+    // An episode stepped over with E ends before the grid has finished
+    model.previous_ending = Some(model.grid.termination().map_or_else(|| "skipped".to_string(), |termination| termination.to_string()));
 
-        // Decide if the agent should explore or exploit
-        let mut rng = rand::thread_rng();
-        let explore = (rng.gen::<f32>() < model.agent.epsilon) || (model.agent.state_space.len() < 5);
+    start_grid(model, model.best.clone(), model.best_value);
+}
 
-        // If the agent is exploring, get a new state from the agent
-        // Otherwise, generate new states by evolving the state space
-        if explore {
-            model.agent.explore();
-        } else {
-            model.agent.exploit();
-        };
+// Replaces the grid with a new one starting from the state
+fn start_grid(model: &mut Model, state: BitVec, value: Option<f32>) {
+    model.grid = Grid::new(&model.config.grid, &state);
+    model.grid.record_history();
+    model.seed = state;
+    model.seed_value = value;
+    model.editing = false;
+}
 
-        // Update agent - With new states having been added to the state space, we need to update the agent
-        model.agent.update(w, h);
+// Paints or erases the cell under the point with whichever mouse button is held
+fn paint(model: &mut Model, point: Point2) {
+    let Some(alive) = model.brush else {
+        return;
+    };
 
-        let grid_state = model.agent.get_best_state();
+    if let Some((x, y)) = model.layout.cell_at(point) {
+        model.grid.set(x, y, alive);
+        model.editing = true;
+    }
+}
 
-        // Reset grid
-        model.grid = Grid::new(w as f32, h as f32, &grid_state);
-    } else {
-        // Update the grid and increase the population age
-        model.grid.population_age += 1;
-        model.grid.update();
+// Sends the drawing to the agent to be evaluated, and plays it from the start in the meantime
+fn submit_drawing(model: &mut Model) {
+    let state = model.grid.bits.to_state();
+    if state.not_any() {
+        println!("There is nothing drawn to submit");
+        return;
     }
+
+    model.trainer.send(Command::Submit(state.clone()));
+    start_grid(model, state, None);
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -149,32 +341,131 @@ fn view(app: &App, model: &Model, frame: Frame) {
     // Set the background to black
     draw.background().color(BLACK);
 
-    for (_, cell) in model.grid.cells.iter().enumerate() {
-        // Determine the cell color based on its state
-        let cell_color = if cell.state { WHITE } else { BLACK };
-        let stroke_color = if cell.state { BLACK } else { WHITE };
-    
-        draw.rect()
-            .xy(cell.pos)
-            .w_h(model.grid.cell_width, model.grid.cell_height)
-            .color(cell_color)
-            .stroke(stroke_color)
-            .stroke_weight(0.5);
-    }    
+    model.renderer.draw(&draw, &model.layout);
+
+    if model.show_hud {
+        draw_hud(&draw, app.window_rect(), &hud_lines(model));
+    }
 
     // Write to the window frame.
     draw.to_frame(app, &frame).unwrap();
 }
 
-fn get_num_cells(window_width: f32, window_height: f32) -> usize {
-    let cell_width = constants::SCALE * window_width;
-    let cell_height = constants::SCALE * window_height;
-    let cols = f32::floor(window_width / cell_width) as usize;
-    let rows = f32::floor(window_height / cell_height) as usize;
+fn hud_lines(model: &Model) -> Vec<String> {
+    let seed_value = if model.editing {
+        "being drawn".to_string()
+    } else {
+        model.seed_value.map_or_else(|| "not evaluated yet".to_string(), |value| value.to_string())
+    };
+
+    let mut lines = vec![
+        format!("Generation {}", model.grid.population_age),
+        format!("Population {}", model.grid.population),
+        format!("Seed value {}", seed_value),
+        format!("Best value {}", model.best_value.map_or_else(|| "none yet".to_string(), |value| value.to_string())),
+    ];
+
+    match &model.progress {
+        Some(progress) => lines.extend([
+            format!("Epsilon {}", progress.epsilon),
+            format!("States {}", progress.states),
+            format!("Rounds {}", progress.rounds),
+            format!("Last round {}, {} states in {:.2?}", progress.round.decision, progress.round.states_evaluated, progress.round.duration),
+        ]),
+        None => lines.push("Training has not finished a round yet".to_string()),
+    }
+
+    lines.extend([
+        format!("Previous episode {}", model.previous_ending.as_deref().unwrap_or("none yet")),
+        format!("Training {}", if model.training_paused { "paused, E steps one episode" } else { "running" }),
+        format!("Speed {}", model.clock),
+    ]);
+
+    lines
+}
+
+// Writes the lines in the top left corner of the window, over a dark panel so they stay readable over live cells
+fn draw_hud(draw: &Draw, window_rect: Rect, lines: &[String]) {
+    let font_size = 14;
+    let line_height = font_size as f32 * 1.4;
+    let padding = 8.0;
+    let width = 300.0_f32.min(window_rect.w());
+    let height = lines.len() as f32 * line_height + 2.0 * padding;
+
+    let panel = Rect::from_w_h(width, height).top_left_of(window_rect);
+    draw.rect().xy(panel.xy()).wh(panel.wh()).color(rgba(0.0, 0.0, 0.0, 0.75));
+
+    draw.text(&lines.join("\n"))
+        .xy(panel.xy())
+        .wh(panel.pad(padding).wh())
+        .font_size(font_size)
+        .line_spacing(line_height - font_size as f32)
+        .left_justify()
+        .align_text_top()
+        .color(rgb(0.3, 1.0, 0.3));
+}
+
+// Reads the config file given with --config, or config.toml if there is one, and applies every --set on top of it
+// Everything else on the command line is a pattern to import
+fn load_config() -> (Config, Vec<String>) {
+    let mut path = Path::new(CONFIG_PATH).exists().then(|| PathBuf::from(CONFIG_PATH));
+    let mut overrides = Vec::new();
+    let mut patterns = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage_error(&format!("{} needs a value", arg)));
+
+        match arg.as_str() {
+            "--config" => path = Some(PathBuf::from(value())),
+            "--set" => overrides.push(value()),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with("--") => usage_error(&format!("Unknown option {}", flag)),
+            _ => patterns.push(arg),
+        }
+    }
+
+    let config = match Config::load(path.as_deref(), &overrides) {
+        Ok(config) => config,
+        Err(e) => fail(&format!("Invalid config: {}", e)),
+    };
+
+    (config, patterns)
+}
+
+// The checkpoint decides the grid's size, rule and boundary, since its states were evaluated with them
+fn load_checkpoint(config: &Config) -> Option<Agent> {
+    let path = &config.files.checkpoint;
+    if !Path::new(path).exists() {
+        return None;
+    }
+
+    match Checkpoint::load(path).and_then(|checkpoint| checkpoint.to_agent(config, Box::new(config.agent.fitness))) {
+        Ok(agent) => {
+            println!("Resumed training from {} with {} states", path, agent.state_space.len());
+            Some(agent)
+        }
+        Err(e) => fail(&format!("Could not load the checkpoint {}: {}", path, e)),
+    }
+}
+
+fn exit(_app: &App, model: Model) {
+    model.trainer.stop();
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
 
-    cols * rows
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
 }
 
 fn main() {
-    nannou::app(model).update(update).run();
+    nannou::app(model).update(update).exit(exit).run();
 }
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::agent::{Agent, Decision, TrainingRound};
+use crate::ga;
+
+// How the agent stood after one round of training
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpisodeMetrics {
+    pub episode: usize,
+    pub decision: Decision,
+    pub states_evaluated: usize,
+    pub state_space_size: usize,
+    // Taken over every evaluated state in the state space
+    pub best_value: f32,
+    pub mean_value: f32,
+    pub median_value: f32,
+    pub epsilon: f32,
+    // The fraction of cells two states of the state space differ in, on average
+    pub diversity: f32,
+    // Time spent on this round of training, and since the log was created
+    pub training_seconds: f64,
+    pub wall_seconds: f64,
+}
+
+impl EpisodeMetrics {
+    pub fn new(episode: usize, agent: &Agent, round: &TrainingRound, wall_seconds: f64) -> Self {
+        let mut values: Vec<f32> = agent.state_space.values().copied().filter(|value| !value.is_nan()).collect();
+        values.sort_by(f32::total_cmp);
+
+        let (best_value, mean_value, median_value) = match values.len() {
+            0 => (0.0, 0.0, 0.0),
+            n => {
+                let median = if n % 2 == 0 { (values[n / 2 - 1] + values[n / 2]) / 2.0 } else { values[n / 2] };
+                (values[n - 1], values.iter().sum::<f32>() / n as f32, median)
+            }
+        };
+
+        EpisodeMetrics {
+            episode,
+            decision: round.decision,
+            states_evaluated: round.states_evaluated,
+            state_space_size: agent.state_space.len(),
+            best_value,
+            mean_value,
+            median_value,
+            epsilon: agent.epsilon,
+            diversity: ga::diversity(&agent.state_space),
+            training_seconds: round.duration.as_secs_f64(),
+            wall_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    // One JSON object per line
+    JsonLines,
+}
+
+const CSV_HEADER: &str = "episode,decision,states_evaluated,state_space_size,best_value,mean_value,median_value,epsilon,diversity,training_seconds,wall_seconds";
+
+#[derive(Debug)]
+pub enum MetricsError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    // The file extension is neither .csv nor .jsonl
+    UnknownFormat(String),
+}
+
+impl fmt::Display for MetricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricsError::Io(e) => write!(f, "{}", e),
+            MetricsError::Format(e) => write!(f, "{}", e),
+            MetricsError::UnknownFormat(extension) => {
+                write!(f, "unknown metrics format \"{}\", expected .csv or .jsonl", extension)
+            }
+        }
+    }
+}
+
+impl Error for MetricsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MetricsError::Io(e) => Some(e),
+            MetricsError::Format(e) => Some(e),
+            MetricsError::UnknownFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MetricsError {
+    fn from(e: io::Error) -> Self {
+        MetricsError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for MetricsError {
+    fn from(e: serde_json::Error) -> Self {
+        MetricsError::Format(e)
+    }
+}
+
+// Writes a record of every round of training to a file, in the format named by its extension
+// Each record is flushed as soon as it is written, so the file can be followed while training runs
+pub struct MetricsLog {
+    writer: BufWriter<File>,
+    format: MetricsFormat,
+    start: Instant,
+    episode: usize,
+}
+
+impl MetricsLog {
+    // Starts a new log, replacing whatever the file held
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, MetricsError> {
+        let format = format_of(path.as_ref())?;
+        MetricsLog::open(File::create(path)?, format, 0)
+    }
+
+    // Carries on the log of a resumed run, numbering its records on from the episodes already trained
+    pub fn append<P: AsRef<Path>>(path: P, episodes: usize) -> Result<Self, MetricsError> {
+        let format = format_of(path.as_ref())?;
+        MetricsLog::open(OpenOptions::new().create(true).append(true).open(path)?, format, episodes)
+    }
+
+    fn open(file: File, format: MetricsFormat, episode: usize) -> Result<Self, MetricsError> {
+        // Only a new file needs the header, one being appended to has it already
+        let empty = file.metadata()?.len() == 0;
+
+        let mut writer = BufWriter::new(file);
+        if format == MetricsFormat::Csv && empty {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            writer.flush()?;
+        }
+
+        Ok(MetricsLog { writer, format, start: Instant::now(), episode })
+    }
+
+    pub fn record(&mut self, agent: &Agent, round: &TrainingRound) -> Result<EpisodeMetrics, MetricsError> {
+        self.episode += 1;
+        let metrics = EpisodeMetrics::new(self.episode, agent, round, self.start.elapsed().as_secs_f64());
+
+        match self.format {
+            MetricsFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{:.6},{:.3}",
+                metrics.episode,
+                metrics.decision,
+                metrics.states_evaluated,
+                metrics.state_space_size,
+                metrics.best_value,
+                metrics.mean_value,
+                metrics.median_value,
+                metrics.epsilon,
+                metrics.diversity,
+                metrics.training_seconds,
+                metrics.wall_seconds
+            )?,
+            MetricsFormat::JsonLines => writeln!(self.writer, "{}", serde_json::to_string(&metrics)?)?,
+        }
+        self.writer.flush()?;
+
+        Ok(metrics)
+    }
+}
+
+fn format_of(path: &Path) -> Result<MetricsFormat, MetricsError> {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase().as_str() {
+        "csv" => Ok(MetricsFormat::Csv),
+        "jsonl" => Ok(MetricsFormat::JsonLines),
+        other => Err(MetricsError::UnknownFormat(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use bitvec::prelude::*;
+    use crate::config::Config;
+
+    fn agent_with_values(values: &[f32]) -> Agent {
+        let mut config = Config::default();
+        config.grid.columns = 4;
+        config.grid.rows = 4;
+        config.agent.seed = Some(0);
+        let mut agent = Agent::new(&config, Box::new(config.agent.fitness));
+
+        for (i, &value) in values.iter().enumerate() {
+            let mut state = bitvec![0; 16];
+            state.set(i, true);
+            agent.state_space.insert(state, value);
+        }
+        agent
+    }
+
+    fn round() -> TrainingRound {
+        TrainingRound { decision: Decision::Explore, states_evaluated: 1, duration: Duration::from_millis(5) }
+    }
+
+    #[test]
+    fn appending_carries_on_numbering_without_another_header() {
+        let path = std::env::temp_dir().join(format!("game_of_life_metrics_{}.csv", std::process::id()));
+        let agent = agent_with_values(&[0.5]);
+
+        let mut log = MetricsLog::create(&path).unwrap();
+        log.record(&agent, &round()).unwrap();
+        log.record(&agent, &round()).unwrap();
+        drop(log);
+
+        // A run resumed from a checkpoint after 2 episodes carries on from the third
+        let mut log = MetricsLog::append(&path, 2).unwrap();
+        assert_eq!(log.record(&agent, &round()).unwrap().episode, 3);
+        drop(log);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], CSV_HEADER);
+        let episodes: Vec<&str> = lines[1..].iter().map(|line| line.split(',').next().unwrap()).collect();
+        assert_eq!(episodes, ["1", "2", "3"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn values_are_summarised_over_the_evaluated_states() {
+        let odd = EpisodeMetrics::new(1, &agent_with_values(&[0.4, 0.1, f32::NAN, 0.7]), &round(), 0.0);
+        assert_eq!((odd.best_value, odd.median_value), (0.7, 0.4));
+        assert!((odd.mean_value - 0.4).abs() < 1e-6);
+        assert_eq!(odd.state_space_size, 4);
+
+        let even = EpisodeMetrics::new(1, &agent_with_values(&[0.4, 0.1, 0.2, 0.7]), &round(), 0.0);
+        assert!((even.median_value - 0.3).abs() < 1e-6);
+
+        let empty = EpisodeMetrics::new(1, &agent_with_values(&[]), &round(), 0.0);
+        assert_eq!((empty.best_value, empty.mean_value, empty.median_value), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt;
+use std::io;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use bitvec::prelude::*;
+
+use crate::agent::Agent;
+use crate::rule::{Rule, ParseRuleError};
+use crate::{rle, plaintext, lif};
+use crate::lif::LifVersion;
+
+// Most cells a pattern read from a file may span, so a corrupt or hostile size cannot exhaust memory
+pub const MAX_PATTERN_CELLS: usize = 1 << 26;
+
+// A pattern cropped to its own width and height, independent of any grid
+// Cells are laid out row by row, like a grid state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    pub cells: BitVec,
+    pub rule: Option<Rule>,
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    // A malformed line in a pattern file, counting from 1
+    Parse { line: usize, message: String },
+    Rule(ParseRuleError),
+    // The pattern is larger than the grid and the placement does not allow clipping
+    DoesNotFit { width: usize, height: usize, columns: usize, rows: usize },
+    // The file extension is not one of the supported pattern formats
+    UnknownFormat(String),
+}
+
+// Where a pattern goes on a grid, and what happens when it does not fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placement {
+    // In the middle of the grid, clipping the edges which do not fit
+    #[default]
+    Centered,
+    // In the top left corner of the grid, clipping the right and bottom if they do not fit
+    TopLeft,
+    // In the middle of the grid, or an error if the pattern does not fit
+    Reject,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(e) => write!(f, "{}", e),
+            PatternError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            PatternError::Rule(e) => write!(f, "invalid rule: {}", e),
+            PatternError::DoesNotFit { width, height, columns, rows } => {
+                write!(f, "a {}x{} pattern does not fit on a {}x{} grid", width, height, columns, rows)
+            }
+            PatternError::UnknownFormat(extension) => {
+                write!(f, "unknown pattern format \"{}\", expected .rle, .cells, .lif or .life", extension)
+            }
+        }
+    }
+}
+
+impl Error for PatternError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PatternError::Io(e) => Some(e),
+            PatternError::Rule(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PatternError {
+    fn from(e: io::Error) -> Self {
+        PatternError::Io(e)
+    }
+}
+
+impl From<ParseRuleError> for PatternError {
+    fn from(e: ParseRuleError) -> Self {
+        PatternError::Rule(e)
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::Centered => write!(f, "centered"),
+            Placement::TopLeft => write!(f, "top-left"),
+            Placement::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePlacementError(pub String);
+
+impl fmt::Display for ParsePlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown placement \"{}\", expected \"centered\", \"top-left\" or \"reject\"", self.0)
+    }
+}
+
+impl Error for ParsePlacementError {}
+
+impl FromStr for Placement {
+    type Err = ParsePlacementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "centered" | "center" => Ok(Placement::Centered),
+            "top-left" | "topleft" => Ok(Placement::TopLeft),
+            "reject" => Ok(Placement::Reject),
+            _ => Err(ParsePlacementError(s.to_string())),
+        }
+    }
+}
+
+// Reads a pattern in whichever format its file extension names
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Pattern, PatternError> {
+    match extension(path.as_ref()).as_str() {
+        "rle" => rle::read_file(path),
+        "cells" => plaintext::read_file(path),
+        "lif" | "life" => lif::read_file(path),
+        other => Err(PatternError::UnknownFormat(other.to_string())),
+    }
+}
+
+// Writes a pattern in whichever format its file extension names, using Life 1.06 for .lif and .life
+pub fn write_file<P: AsRef<Path>>(path: P, pattern: &Pattern) -> Result<(), PatternError> {
+    match extension(path.as_ref()).as_str() {
+        "rle" => rle::write_file(path, pattern),
+        "cells" => plaintext::write_file(path, pattern),
+        "lif" | "life" => lif::write_file(path, pattern, LifVersion::V106),
+        other => Err(PatternError::UnknownFormat(other.to_string())),
+    }
+}
+
+// Reads every pattern file and adds it to the agent's state space, placed as its config says
+// Files which cannot be read or placed are skipped, and a pattern made for another rule runs under the agent's
+pub fn import_into(agent: &mut Agent, paths: &[String]) {
+    let placement = agent.config.files.pattern_placement;
+
+    for path in paths {
+        let pattern = match read_file(path) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                eprintln!("Could not import {}: {}", path, e);
+                continue;
+            }
+        };
+
+        if let Some(rule) = pattern.rule.filter(|&rule| rule != agent.rule) {
+            println!("Importing {} which was made for {}, but it will run under {}", path, rule, agent.rule);
+        }
+
+        match pattern.to_state(agent.columns, agent.rows, placement) {
+            Ok(state) => agent.add_state(state),
+            Err(e) => eprintln!("Could not import {}: {}", path, e),
+        }
+    }
+}
+
+// Whether a pattern of the given size stays within MAX_PATTERN_CELLS
+pub(crate) fn check_size(width: usize, height: usize) -> Result<(), String> {
+    match width.checked_mul(height) {
+        Some(cells) if cells <= MAX_PATTERN_CELLS => Ok(()),
+        _ => Err(format!("a {}x{} pattern is larger than the {} cells supported", width, height, MAX_PATTERN_CELLS)),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase()
+}
+
+impl Pattern {
+    pub fn new(width: usize, height: usize) -> Self {
+        Pattern { width, height, cells: bitvec![0; width * height], rule: None, comments: Vec::new() }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.cells.set(y * self.width + x, alive);
+    }
+
+    // Crops a grid state down to the bounding box of its live cells
+    pub fn from_state(state: &BitVec, columns: usize, rows: usize) -> Self {
+        let live: Vec<(usize, usize)> = state
+            .iter_ones()
+            .filter(|&idx| idx < columns * rows)
+            .map(|idx| (idx % columns, idx / columns))
+            .collect();
+
+        if live.is_empty() {
+            return Pattern::new(0, 0);
+        }
+
+        let min_x = live.iter().map(|&(x, _)| x).min().unwrap();
+        let max_x = live.iter().map(|&(x, _)| x).max().unwrap();
+        let min_y = live.iter().map(|&(_, y)| y).min().unwrap();
+        let max_y = live.iter().map(|&(_, y)| y).max().unwrap();
+
+        let mut pattern = Pattern::new(max_x - min_x + 1, max_y - min_y + 1);
+        for (x, y) in live {
+            pattern.set(x - min_x, y - min_y, true);
+        }
+
+        pattern
+    }
+
+    // Builds the grid state holding the pattern, clipping whatever does not fit unless the placement rejects it
+    pub fn to_state(&self, columns: usize, rows: usize, placement: Placement) -> Result<BitVec, PatternError> {
+        let fits = self.width <= columns && self.height <= rows;
+        if placement == Placement::Reject && !fits {
+            return Err(PatternError::DoesNotFit { width: self.width, height: self.height, columns, rows });
+        }
+
+        let (x_offset, y_offset) = match placement {
+            Placement::TopLeft => (0, 0),
+            Placement::Centered | Placement::Reject => {
+                ((columns as i64 - self.width as i64) / 2, (rows as i64 - self.height as i64) / 2)
+            }
+        };
+
+        let mut state = bitvec![0; columns * rows];
+
+        for idx in self.cells.iter_ones() {
+            let x = (idx % self.width) as i64 + x_offset;
+            let y = (idx / self.width) as i64 + y_offset;
+
+            if (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y) {
+                state.set(y as usize * columns + x as usize, true);
+            }
+        }
+
+        Ok(state)
+    }
+}
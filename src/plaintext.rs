@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::Path;
+
+use crate::pattern::{Pattern, PatternError};
+
+// Reads a pattern in the plaintext (.cells) format
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Pattern, PatternError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn write_file<P: AsRef<Path>>(path: P, pattern: &Pattern) -> Result<(), PatternError> {
+    fs::write(path, write(pattern))?;
+    Ok(())
+}
+
+// Lines starting with '!' are comments, every other line is a row of dead ('.') and alive ('O') cells
+// Rows may be cut short after their last live cell, so the width is that of the longest row
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut comments = Vec::new();
+    let mut rows: Vec<Vec<bool>> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+
+        if let Some(comment) = line.strip_prefix('!') {
+            comments.push(comment.trim().to_string());
+            continue;
+        }
+
+        let row = line
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(false),
+                // Some files use '*' for live cells
+                'O' | 'o' | '*' => Ok(true),
+                c => Err(PatternError::Parse { line: i + 1, message: format!("unexpected character '{}'", c) }),
+            })
+            .collect::<Result<Vec<bool>, PatternError>>()?;
+        rows.push(row);
+    }
+
+    // Blank lines at either end of the file are not part of the pattern, only those between rows are
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+    let leading_blank_lines = rows.iter().take_while(|row| row.is_empty()).count();
+    rows.drain(..leading_blank_lines);
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut pattern = Pattern::new(width, rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &alive) in row.iter().enumerate() {
+            pattern.set(x, y, alive);
+        }
+    }
+    pattern.comments = comments;
+
+    Ok(pattern)
+}
+
+// The format has no place for a rule, so only the comments and cells are written
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+
+    for comment in &pattern.comments {
+        text.push_str(&format!("!{}\n", comment));
+    }
+
+    for y in 0..pattern.height {
+        let row: String = (0..pattern.width).map(|x| if pattern.get(x, y) { 'O' } else { '.' }).collect();
+        text.push_str(&row);
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_round_trips_through_parse() {
+        let mut pattern = Pattern::new(5, 4);
+        for (x, y) in [(1, 1), (2, 1), (4, 3)] {
+            pattern.set(x, y, true);
+        }
+        pattern.comments = vec!["Name: test".to_string(), String::new()];
+
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn ignores_blank_lines_at_both_ends() {
+        let pattern = parse("!Name: blinker\n\n\nOOO\n\n.O\n\n\n").unwrap();
+
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells.iter_ones().collect::<Vec<_>>(), vec![0, 1, 2, 7]);
+    }
+}
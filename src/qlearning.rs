@@ -0,0 +1,218 @@
+use rand::Rng;
+use bitvec::prelude::*;
+
+use crate::config::QLearningConfig;
+
+// Number of features besides each cell's own: one per number of live neighbours a cell can have, and a bias
+const NEIGHBOUR_FEATURES: usize = 9;
+const EXTRA_FEATURES: usize = NEIGHBOUR_FEATURES + 1;
+
+// Learns which cells to place, one at a time, to build seeds which score well
+//
+// The MDP: a state is the seed built so far, starting from an empty grid, and an action places a live cell on an empty
+// one. After the configured number of placements the episode ends, and the reward is the score of the seed's run.
+// Q is linear in the features of the action: which cell it is, how many live neighbours that cell already has, and a bias.
+pub struct QLearner {
+    pub weights: Vec<f32>,
+    pub learning_rate: f32,
+    pub discount: f32,
+    pub placements: usize,
+
+    columns: usize,
+    rows: usize,
+}
+
+// The cells placed during one episode, in order
+pub struct QEpisode {
+    pub seed: BitVec,
+    pub actions: Vec<usize>,
+    // Whether any of the placements was a random one rather than the best known
+    pub explored: bool,
+}
+
+impl QLearner {
+    pub fn new(config: &QLearningConfig, columns: usize, rows: usize) -> Self {
+        let num_cells = columns * rows;
+
+        QLearner {
+            weights: vec![0.0; num_cells + EXTRA_FEATURES],
+            learning_rate: config.learning_rate,
+            discount: config.discount,
+            placements: config.placements.min(num_cells),
+            columns,
+            rows,
+        }
+    }
+
+    // Builds a seed by placing cells epsilon-greedily
+    pub fn play(&self, epsilon: f32, rng: &mut impl Rng) -> QEpisode {
+        let num_cells = self.columns * self.rows;
+        let mut seed = bitvec![0; num_cells];
+        let mut actions = Vec::with_capacity(self.placements);
+        let mut explored = false;
+
+        for _ in 0..self.placements {
+            let action = if rng.gen::<f32>() < epsilon {
+                explored = true;
+                let empty: Vec<usize> = seed.iter_zeros().collect();
+                empty[rng.gen_range(0..empty.len())]
+            } else {
+                self.best_action(&seed, rng)
+            };
+
+            seed.set(action, true);
+            actions.push(action);
+        }
+
+        QEpisode { seed, actions, explored }
+    }
+
+    // Replays the episode, moving the value of each placement towards the discounted value of the best placement after
+    // it, or towards the reward for the last one
+    pub fn learn(&mut self, episode: &QEpisode, reward: f32) {
+        if !reward.is_finite() {
+            return;
+        }
+
+        let mut seed = bitvec![0; self.columns * self.rows];
+
+        for (step, &action) in episode.actions.iter().enumerate() {
+            let features = self.features(&seed, action);
+            let value = self.value(&features);
+
+            seed.set(action, true);
+            let target = if step + 1 == episode.actions.len() {
+                reward
+            } else {
+                self.discount * self.best_value(&seed)
+            };
+
+            let error = target - value;
+            for feature in features {
+                self.weights[feature] += self.learning_rate * error;
+            }
+        }
+    }
+
+    // The greedy placement, with ties broken at random so an untrained learner still explores every cell
+    fn best_action(&self, seed: &BitVec, rng: &mut impl Rng) -> usize {
+        let mut best = None;
+        let mut best_value = f32::MIN;
+        let mut ties = 0;
+
+        for cell in seed.iter_zeros() {
+            let value = self.value(&self.features(seed, cell));
+            if value > best_value {
+                best = Some(cell);
+                best_value = value;
+                ties = 1;
+            } else if value == best_value {
+                ties += 1;
+                if rng.gen_range(0..ties) == 0 {
+                    best = Some(cell);
+                }
+            }
+        }
+
+        best.expect("Expected an empty cell to place on")
+    }
+
+    fn best_value(&self, seed: &BitVec) -> f32 {
+        seed.iter_zeros()
+            .map(|cell| self.value(&self.features(seed, cell)))
+            .reduce(f32::max)
+            .unwrap_or(0.0)
+    }
+
+    fn value(&self, features: &[usize]) -> f32 {
+        features.iter().map(|&feature| self.weights[feature]).sum()
+    }
+
+    // Every feature is either 0 or 1, so only the indices of those which are 1 are returned
+    fn features(&self, seed: &BitVec, cell: usize) -> [usize; 3] {
+        let num_cells = self.columns * self.rows;
+        [cell, num_cells + self.live_neighbours(seed, cell), num_cells + NEIGHBOUR_FEATURES]
+    }
+
+    fn live_neighbours(&self, seed: &BitVec, cell: usize) -> usize {
+        let (x, y) = ((cell % self.columns) as isize, (cell / self.columns) as isize);
+        let mut count = 0;
+
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx, dy) == (0, 0) || nx < 0 || ny < 0 || nx >= self.columns as isize || ny >= self.rows as isize {
+                    continue;
+                }
+                if seed[ny as usize * self.columns + nx as usize] {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn learner(columns: usize, rows: usize, placements: usize) -> QLearner {
+        let config = QLearningConfig { placements, learning_rate: 0.1, discount: 0.9 };
+        QLearner::new(&config, columns, rows)
+    }
+
+    #[test]
+    fn play_places_each_cell_once() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for epsilon in [0.0, 0.5, 1.0] {
+            let episode = learner(6, 5, 12).play(epsilon, &mut rng);
+
+            let mut actions = episode.actions.clone();
+            actions.sort_unstable();
+            actions.dedup();
+            assert_eq!(actions.len(), 12);
+            assert_eq!(episode.seed.count_ones(), 12);
+            assert!(episode.actions.iter().all(|&action| episode.seed[action]));
+            if epsilon != 0.5 {
+                assert_eq!(episode.explored, epsilon == 1.0);
+            }
+        }
+
+        // There can be no more placements than cells
+        let episode = learner(2, 2, 10).play(0.0, &mut rng);
+        assert_eq!((episode.actions.len(), episode.seed.count_ones()), (4, 4));
+    }
+
+    #[test]
+    fn learn_moves_the_last_placement_towards_the_reward() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut learner = learner(5, 5, 6);
+        let episode = learner.play(1.0, &mut rng);
+
+        // The value of the last placement, from the seed as it was just before it
+        let (&last, earlier) = episode.actions.split_last().unwrap();
+        let mut before_last = bitvec![0; 25];
+        for &action in earlier {
+            before_last.set(action, true);
+        }
+        let last_value = |learner: &QLearner| learner.value(&learner.features(&before_last, last));
+
+        let reward = 0.8;
+        let mut previous = last_value(&learner);
+        for _ in 0..5 {
+            learner.learn(&episode, reward);
+            let value = last_value(&learner);
+            assert!((reward - value).abs() < (reward - previous).abs(), "{} is no closer to {} than {}", value, reward, previous);
+            previous = value;
+        }
+
+        // Rewards which are not numbers teach nothing
+        let weights = learner.weights.clone();
+        learner.learn(&episode, f32::NAN);
+        assert_eq!(learner.weights, weights);
+    }
+}
@@ -0,0 +1,117 @@
+use nannou::prelude::*;
+use nannou::wgpu;
+use nannou::window::Window;
+use bitvec::prelude::{bitvec, BitVec};
+
+use crate::layout::Layout;
+use game_of_life::grid::Grid;
+
+// Cells smaller than this on screen, in either direction, are not outlined since the lines would hide them
+const MIN_OUTLINED_CELL_SIZE: f32 = 4.0;
+
+const LIVE: [u8; 4] = [255, 255, 255, 255];
+// Dead cells are left transparent, showing the background
+const DEAD: [u8; 4] = [0, 0, 0, 0];
+
+// Draws the grid as a texture with one pixel per cell, stretched over the window
+// Only the pixels of cells which changed are rewritten, and the texture is only uploaded when there were any, so
+// drawing a frame takes two triangles however many cells the grid has
+pub struct Renderer {
+    // Whether every cell is outlined
+    pub grid_lines: bool,
+
+    texture: wgpu::Texture,
+    // Four bytes per cell, row by row
+    pixels: Vec<u8>,
+    // The state the texture shows
+    state: BitVec,
+    columns: usize,
+    rows: usize,
+}
+
+impl Renderer {
+    // Grids wider or taller than the largest texture the GPU allows, at least 2048 cells, cannot be drawn
+    pub fn new(window: &Window, columns: usize, rows: usize, grid_lines: bool) -> Result<Self, String> {
+        let max_size = window.device().limits().max_texture_dimension_2d as usize;
+        if columns > max_size || rows > max_size {
+            return Err(format!(
+                "The grid is {}x{} cells, but this GPU can only draw grids of up to {} cells across and down",
+                columns, rows, max_size
+            ));
+        }
+
+        let texture = wgpu::TextureBuilder::new()
+            .size([columns as u32, rows as u32])
+            .format(wgpu::TextureFormat::Rgba8UnormSrgb)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+            .build(window.device());
+
+        let renderer = Renderer {
+            grid_lines,
+            texture,
+            pixels: DEAD.repeat(columns * rows),
+            state: bitvec![0; columns * rows],
+            columns,
+            rows,
+        };
+        renderer.upload(window);
+
+        Ok(renderer)
+    }
+
+    // Brings the texture up to date with the grid
+    pub fn update(&mut self, window: &Window, grid: &Grid) {
+        let state = grid.bits.to_state();
+
+        let mut changed = state.clone();
+        changed ^= self.state.as_bitslice();
+        if changed.not_any() {
+            return;
+        }
+
+        for cell in changed.iter_ones() {
+            let color = if state[cell] { LIVE } else { DEAD };
+            self.pixels[cell * 4..cell * 4 + 4].copy_from_slice(&color);
+        }
+        self.state = state;
+
+        self.upload(window);
+    }
+
+    pub fn draw(&self, draw: &Draw, layout: &Layout) {
+        let rect = layout.rect();
+
+        // Sample the nearest pixel, so cells stay sharp squares however far they are stretched
+        let sampler = wgpu::SamplerBuilder::new()
+            .mag_filter(wgpu::FilterMode::Nearest)
+            .min_filter(wgpu::FilterMode::Nearest)
+            .into_descriptor();
+        draw.sampler(sampler).texture(&self.texture).xy(rect.xy()).wh(rect.wh());
+
+        if self.grid_lines && layout.cell_width.min(layout.cell_height) >= MIN_OUTLINED_CELL_SIZE {
+            self.draw_grid_lines(draw, layout);
+        }
+    }
+
+    // One line along every edge between columns and between rows, and around the grid
+    fn draw_grid_lines(&self, draw: &Draw, layout: &Layout) {
+        let rect = layout.rect();
+        let color = gray(0.4);
+
+        for x in 0..=self.columns {
+            let x = rect.left() + x as f32 * layout.cell_width;
+            draw.line().start(pt2(x, rect.bottom())).end(pt2(x, rect.top())).weight(0.5).color(color);
+        }
+        for y in 0..=self.rows {
+            let y = rect.top() - y as f32 * layout.cell_height;
+            draw.line().start(pt2(rect.left(), y)).end(pt2(rect.right(), y)).weight(0.5).color(color);
+        }
+    }
+
+    fn upload(&self, window: &Window) {
+        let device = window.device();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("grid texture upload") });
+        self.texture.upload_data(device, &mut encoder, &self.pixels);
+        window.queue().submit(Some(encoder.finish()));
+    }
+}
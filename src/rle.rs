@@ -0,0 +1,277 @@
+use std::fs;
+use std::path::Path;
+
+use crate::rule::{Rule, ParseRuleError};
+use crate::pattern::{self, Pattern, PatternError};
+
+// Longest line written to a .rle file, as recommended by the format
+const MAX_LINE_LENGTH: usize = 70;
+
+// Reads a pattern in the run-length encoded (.rle) format
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Pattern, PatternError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub fn write_file<P: AsRef<Path>>(path: P, pattern: &Pattern) -> Result<(), PatternError> {
+    fs::write(path, write(pattern))?;
+    Ok(())
+}
+
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut size: Option<(usize, usize)> = None;
+    let mut rule = None;
+    let mut comments = Vec::new();
+
+    let mut live: Vec<(usize, usize)> = Vec::new();
+    let (mut x, mut y): (usize, usize) = (0, 0);
+    let mut run_count: Option<usize> = None;
+    let mut finished = false;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+
+        if finished {
+            break;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            match comment.chars().next() {
+                Some('C') | Some('c') => comments.push(comment[1..].trim().to_string()),
+                // Older files give the rule on its own line
+                Some('r') => rule = Some(parse_rule(comment[1..].trim())?),
+                _ => {}
+            }
+            continue;
+        }
+
+        // The header comes before the cells, e.g. "x = 3, y = 3, rule = B3/S23"
+        if size.is_none() {
+            let (width, height, header_rule) = parse_header(line).map_err(|message| PatternError::Parse { line: line_number, message })?;
+            size = Some((width, height));
+            rule = header_rule.or(rule);
+            continue;
+        }
+
+        let too_large = || PatternError::Parse { line: line_number, message: "run count too large".to_string() };
+
+        for c in line.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                let count = run_count.unwrap_or(0).checked_mul(10).and_then(|count| count.checked_add(digit as usize));
+                run_count = Some(count.ok_or_else(too_large)?);
+                continue;
+            }
+
+            // Runs may not reach past the largest pattern supported, or they could take all memory to store
+            let count = run_count.take().unwrap_or(1);
+            let (end_x, end_y) = match c {
+                '$' => (x, y.checked_add(count).ok_or_else(too_large)?),
+                _ => (x.checked_add(count).ok_or_else(too_large)?, y),
+            };
+            pattern::check_size(end_x.max(1), end_y + 1).map_err(|message| PatternError::Parse { line: line_number, message })?;
+
+            match c {
+                'b' | '.' => x = end_x,
+                '$' => {
+                    y = end_y;
+                    x = 0;
+                }
+                '!' => {
+                    finished = true;
+                    break;
+                }
+                // Any other state of a multi-state pattern is treated as alive
+                c if c.is_ascii_alphabetic() => {
+                    live.extend((x..end_x).map(|cx| (cx, y)));
+                    x = end_x;
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    return Err(PatternError::Parse { line: line_number, message: format!("unexpected character '{}'", c) });
+                }
+            }
+        }
+    }
+
+    let (width, height) = match size {
+        Some(size) => size,
+        None => return Err(PatternError::Parse { line: text.lines().count(), message: "missing \"x = ..., y = ...\" header".to_string() }),
+    };
+
+    // Be lenient with headers which understate the pattern's size
+    let width = live.iter().map(|&(x, _)| x + 1).max().unwrap_or(0).max(width);
+    let height = live.iter().map(|&(_, y)| y + 1).max().unwrap_or(0).max(height);
+    pattern::check_size(width, height).map_err(|message| PatternError::Parse { line: text.lines().count(), message })?;
+
+    let mut pattern = Pattern::new(width, height);
+    for (x, y) in live {
+        pattern.set(x, y, true);
+    }
+    pattern.rule = rule;
+    pattern.comments = comments;
+
+    Ok(pattern)
+}
+
+fn parse_header(line: &str) -> Result<(usize, usize, Option<Rule>), String> {
+    let mut width = None;
+    let mut height = None;
+    let mut rule = None;
+
+    // The rule comes last and may itself hold commas, as in "rule = B3/S23:T20,20", so it takes the rest of the line
+    let (fields, rule_field) = match line.find("rule") {
+        Some(start) => (&line[..start], Some(&line[start..])),
+        None => (line, None),
+    };
+
+    for field in fields.split(',').chain(rule_field).filter(|field| !field.trim().is_empty()) {
+        let (key, value) = field.split_once('=').ok_or_else(|| format!("expected \"key = value\" in header, found \"{}\"", field.trim()))?;
+        let value = value.trim();
+
+        match key.trim() {
+            "x" => width = Some(value.parse().map_err(|_| format!("invalid width \"{}\"", value))?),
+            "y" => height = Some(value.parse().map_err(|_| format!("invalid height \"{}\"", value))?),
+            "rule" => rule = Some(parse_rule(value).map_err(|e| format!("invalid rule \"{}\": {}", value, e))?),
+            _ => {}
+        }
+    }
+
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            pattern::check_size(width, height)?;
+            Ok((width, height, rule))
+        }
+        _ => Err("header must give both x and y".to_string()),
+    }
+}
+
+// Golly follows the rule with the grid it was made for, e.g. ":T20,20" for a 20x20 torus
+// The grid's own size and boundary are what count here, so only the rule is kept
+fn parse_rule(value: &str) -> Result<Rule, ParseRuleError> {
+    value.split(':').next().unwrap_or(value).trim().parse()
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+
+    for comment in &pattern.comments {
+        text.push_str(&format!("#C {}\n", comment));
+    }
+
+    text.push_str(&format!("x = {}, y = {}", pattern.width, pattern.height));
+    if let Some(rule) = pattern.rule {
+        text.push_str(&format!(", rule = {}", rule));
+    }
+    text.push('\n');
+
+    // Encode every row as runs of dead (b) and alive (o) cells, leaving out trailing dead cells
+    // and merging empty rows into the count of the row separator ($)
+    let mut tokens: Vec<String> = Vec::new();
+    let mut pending_rows = 0;
+
+    for y in 0..pattern.height {
+        let mut runs: Vec<(bool, usize)> = Vec::new();
+        for x in 0..pattern.width {
+            let alive = pattern.get(x, y);
+            match runs.last_mut() {
+                Some((state, count)) if *state == alive => *count += 1,
+                _ => runs.push((alive, 1)),
+            }
+        }
+
+        if let Some(&(false, _)) = runs.last() {
+            runs.pop();
+        }
+
+        if runs.is_empty() {
+            pending_rows += 1;
+            continue;
+        }
+
+        if !tokens.is_empty() {
+            tokens.push(run(pending_rows + 1, '$'));
+        } else if pending_rows > 0 {
+            tokens.push(run(pending_rows, '$'));
+        }
+        pending_rows = 0;
+
+        tokens.extend(runs.into_iter().map(|(alive, count)| run(count, if alive { 'o' } else { 'b' })));
+    }
+    tokens.push("!".to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if line.len() + token.len() > MAX_LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+fn run(count: usize, tag: char) -> String {
+    if count == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", count, tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitvec::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn write_round_trips_through_parse() {
+        let mut pattern = Pattern::new(75, 6);
+        for (x, y) in [(0, 0), (1, 0), (2, 0), (74, 0), (5, 3), (6, 3), (7, 5), (70, 5)] {
+            pattern.set(x, y, true);
+        }
+        pattern.rule = Some("B36/S23".parse().unwrap());
+        pattern.comments = vec!["A test pattern".to_string(), "with two comments".to_string()];
+
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn reads_a_glider() {
+        let pattern = parse("#N Glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n").unwrap();
+
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells, bitvec![0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(pattern.rule, Some(Rule::conway()));
+    }
+
+    #[test]
+    fn keeps_the_rule_of_a_bounded_grid() {
+        let pattern = parse("x = 2, y = 1, rule = B3/S23:T20,20\n2o!\n").unwrap();
+        assert_eq!(pattern.rule, Some(Rule::conway()));
+
+        let pattern = parse("#r B36/S23:P10,10\nx = 2, y = 1\n2o!\n").unwrap();
+        assert_eq!(pattern.rule, Some("B36/S23".parse().unwrap()));
+    }
+
+    #[test]
+    fn rejects_sizes_too_large_to_hold() {
+        for text in [
+            "x = 3, y = 1\n99999999999999999999999o!\n",
+            "x = 3, y = 1\n999999999o!\n",
+            "x = 3, y = 1\no999999999$o!\n",
+            "x = 99999999999, y = 99999999999\no!\n",
+            "x = 18446744073709551615, y = 2\no!\n",
+        ] {
+            assert!(matches!(parse(text), Err(PatternError::Parse { .. })), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+
+// An outer-totalistic Life-like rule, stored as bitmasks over the live neighbour count (0..=8)
+// Bit n of `birth` is set if a dead cell with n live neighbours is born,
+// bit n of `survival` is set if a live cell with n live neighbours survives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rule {
+    pub birth: u16,
+    pub survival: u16,
+}
+
+impl Rule {
+    // Conway's Game of Life, B3/S23
+    pub fn conway() -> Self {
+        Rule { birth: 1 << 3, survival: (1 << 2) | (1 << 3) }
+    }
+
+    // Returns the next state of a cell given its current state and its number of live neighbours
+    pub fn next_state(&self, alive: bool, live_neighbors: usize) -> bool {
+        let mask = if alive { self.survival } else { self.birth };
+        live_neighbors <= 8 && mask & (1 << live_neighbors) != 0
+    }
+
+    pub fn births_on(&self, live_neighbors: usize) -> bool {
+        self.next_state(false, live_neighbors)
+    }
+
+    pub fn survives_on(&self, live_neighbors: usize) -> bool {
+        self.next_state(true, live_neighbors)
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::conway()
+    }
+}
+
+impl fmt::Display for Rule {
+    // Formats the rule in standard B/S notation, e.g. "B36/S23"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "B")?;
+        for n in (0..=8).filter(|&n| self.births_on(n)) {
+            write!(f, "{}", n)?;
+        }
+
+        write!(f, "/S")?;
+        for n in (0..=8).filter(|&n| self.survives_on(n)) {
+            write!(f, "{}", n)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRuleError {
+    // The rule does not have exactly two parts, separated by '/' or by starting each with B or S
+    MissingSeparator,
+    // The same part (B or S) was given twice, e.g. "B3/B23"
+    DuplicatePart(char),
+    // A neighbour count outside of 0..=8 or an unexpected character
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRuleError::MissingSeparator => write!(f, "expected a rule of the form B<digits>/S<digits>"),
+            ParseRuleError::DuplicatePart(part) => write!(f, "rule part '{}' given more than once", part),
+            ParseRuleError::InvalidCharacter(c) => write!(f, "invalid character '{}' in rule", c),
+        }
+    }
+}
+
+impl Error for ParseRuleError {}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    // Parses rules in B/S notation ("B36/S23", "s23/b3"), without the slash as Golly writes them ("B36S23"),
+    // as well as the older S/B notation ("23/36")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut parts: Vec<&str> = s.split('/').collect();
+
+        // Without a slash, the second part starts at the second B or S
+        if parts.len() == 1 {
+            if let Some(i) = s.char_indices().skip(1).find(|(_, c)| matches!(c.to_ascii_uppercase(), 'B' | 'S')).map(|(i, _)| i) {
+                parts = vec![&s[..i], &s[i..]];
+            }
+        }
+
+        if parts.len() != 2 {
+            return Err(ParseRuleError::MissingSeparator);
+        }
+
+        let mut birth = None;
+        let mut survival = None;
+
+        for (i, part) in parts.iter().enumerate() {
+            let part = part.trim();
+            let (kind, digits) = match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') => ('B', &part[1..]),
+                Some('S') => ('S', &part[1..]),
+                // Without a prefix, fall back to S/B notation where survival comes first
+                _ => (if i == 0 { 'S' } else { 'B' }, part),
+            };
+
+            let mut mask = 0u16;
+            for c in digits.chars() {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 => mask |= 1 << n,
+                    _ => return Err(ParseRuleError::InvalidCharacter(c)),
+                }
+            }
+
+            let slot = if kind == 'B' { &mut birth } else { &mut survival };
+            if slot.is_some() {
+                return Err(ParseRuleError::DuplicatePart(kind));
+            }
+            *slot = Some(mask);
+        }
+
+        match (birth, survival) {
+            (Some(birth), Some(survival)) => Ok(Rule { birth, survival }),
+            _ => Err(ParseRuleError::MissingSeparator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_notation_of_the_same_rule() {
+        for text in ["B3/S23", "b3/s23", "S23/B3", "23/3", "B3S23", "b3s23", " B3/S23 "] {
+            assert_eq!(text.parse::<Rule>(), Ok(Rule::conway()), "parsing {:?}", text);
+        }
+    }
+
+    #[test]
+    fn display_round_trips_through_parsing() {
+        for text in ["B3/S23", "B36/S23", "B0123478/S34678", "B/S", "B012345678/S012345678"] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(rule.to_string(), text);
+            assert_eq!(rule.to_string().parse::<Rule>(), Ok(rule));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert_eq!("B3".parse::<Rule>(), Err(ParseRuleError::MissingSeparator));
+        assert_eq!("B3/S23/S1".parse::<Rule>(), Err(ParseRuleError::MissingSeparator));
+        assert_eq!("B3/B23".parse::<Rule>(), Err(ParseRuleError::DuplicatePart('B')));
+        assert_eq!("B39/S23".parse::<Rule>(), Err(ParseRuleError::InvalidCharacter('9')));
+        assert_eq!("B3S2x".parse::<Rule>(), Err(ParseRuleError::InvalidCharacter('x')));
+    }
+}
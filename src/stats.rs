@@ -0,0 +1,31 @@
+use crate::bitgrid::BitGrid;
+use crate::hashlife::BoundingBox;
+
+// What a grid looked like at one generation, and how it got there from the one before
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub population: usize,
+    pub births: usize,
+    pub deaths: usize,
+    // Cells which were born or died, i.e. births + deaths
+    pub changed: usize,
+    // Both are None once the population has died out
+    pub bounding_box: Option<BoundingBox>,
+    pub centroid: Option<(f32, f32)>,
+}
+
+impl GenerationStats {
+    // The first generation recorded has no previous one, so nothing was born and nothing died
+    pub fn new(generation: usize, bits: &BitGrid, previous: Option<&BitGrid>) -> Self {
+        let (births, deaths) = previous.map_or((0, 0), |previous| bits.changes(previous));
+
+        let population = bits.population();
+        let (sum_x, sum_y) = bits.live_cells().fold((0.0, 0.0), |(sum_x, sum_y), (x, y)| (sum_x + x as f32, sum_y + y as f32));
+
+        let bounding_box = bits.bounding_box();
+        let centroid = (population > 0).then(|| (sum_x / population as f32, sum_y / population as f32));
+
+        GenerationStats { generation, population, births, deaths, changed: births + deaths, bounding_box, centroid }
+    }
+}
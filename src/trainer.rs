@@ -0,0 +1,230 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryIter};
+use std::thread::{self, JoinHandle};
+
+use bitvec::prelude::BitVec;
+
+use game_of_life::agent::{Agent, TrainingRound};
+use game_of_life::config::Config;
+use game_of_life::checkpoint::Checkpoint;
+use game_of_life::metrics::MetricsLog;
+use game_of_life::pattern::{self, Pattern};
+
+// What the viewer asks of the agent
+pub enum Command {
+    // Add a drawing to the state space and evaluate it straight away
+    Submit(BitVec),
+    // Throw the agent away for a new one with the same settings but a new seed
+    Reset,
+    // Stop training between rounds, or carry on again
+    TogglePause,
+    // Run a single round of training, then stay paused
+    Step,
+    SaveBestSeed,
+    SaveCheckpoint,
+    // Save a checkpoint and stop training
+    Stop,
+}
+
+// What the agent tells the viewer
+pub enum Report {
+    Progress(Progress),
+    // The best seed changed, either because a better one was found or because the old one was pruned
+    Best { state: BitVec, value: f32 },
+    // A submitted drawing was evaluated, with no value when it scored too low to be kept
+    Evaluated { state: BitVec, value: Option<f32> },
+    // The agent was reset, and its first state is ready to be played
+    Reset(BitVec),
+}
+
+// How the agent stood after a round of training
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub round: TrainingRound,
+    // Rounds trained since the agent was created or reset
+    pub rounds: usize,
+    pub best_value: f32,
+    pub epsilon: f32,
+    pub states: usize,
+}
+
+// Trains the agent on a thread of its own, as fast as it can unless paused, so the window never waits on it
+// Commands are only handled between rounds of training, so one may wait for the round in progress to finish
+pub struct Trainer {
+    commands: Sender<Command>,
+    reports: Receiver<Report>,
+    handle: JoinHandle<()>,
+}
+
+impl Trainer {
+    pub fn spawn(agent: Agent, metrics: Option<MetricsLog>) -> Self {
+        let (commands, command_receiver) = mpsc::channel();
+        let (report_sender, reports) = mpsc::channel();
+
+        let handle = thread::spawn(move || train(agent, metrics, command_receiver, report_sender));
+
+        Trainer { commands, reports, handle }
+    }
+
+    pub fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            eprintln!("Training has stopped, so the command was ignored");
+        }
+    }
+
+    // Every report sent since the last call, without waiting for more
+    pub fn reports(&self) -> TryIter<'_, Report> {
+        self.reports.try_iter()
+    }
+
+    // Waits for the trainer to save a checkpoint and finish
+    pub fn stop(self) {
+        self.send(Command::Stop);
+        if self.handle.join().is_err() {
+            eprintln!("Training stopped with an error");
+        }
+    }
+}
+
+fn train(mut agent: Agent, mut metrics: Option<MetricsLog>, commands: Receiver<Command>, reports: Sender<Report>) {
+    let mut rounds = 0;
+    let mut best = None;
+    let mut paused = false;
+
+    loop {
+        // While paused there is nothing to do until a command comes in, so wait for one instead of spinning
+        let waiting = if paused {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => return,
+            }
+        } else {
+            None
+        };
+
+        let mut step = false;
+        for command in waiting.into_iter().chain(commands.try_iter()) {
+            match command {
+                Command::Submit(state) => {
+                    let value = submit(&mut agent, &state);
+                    if reports.send(Report::Evaluated { state, value }).is_err() {
+                        return;
+                    }
+                }
+                Command::Reset => {
+                    let mut config = agent.config.clone();
+                    config.agent.seed = None;
+                    // The metrics log carries on counting episodes, so checkpoints of the new agent have to as well
+                    let episodes = agent.episodes;
+                    agent = Agent::new(&config, Box::new(config.agent.fitness));
+                    agent.episodes = episodes;
+                    save_effective_config(&agent.config);
+
+                    rounds = 0;
+                    best = None;
+                    if reports.send(Report::Reset(agent.get_new_state())).is_err() {
+                        return;
+                    }
+                }
+                Command::TogglePause => paused = !paused,
+                Command::Step => {
+                    paused = true;
+                    step = true;
+                }
+                Command::SaveBestSeed => save_best_seed(&agent),
+                Command::SaveCheckpoint => save_checkpoint(&agent),
+                Command::Stop => {
+                    save_checkpoint(&agent);
+                    return;
+                }
+            }
+        }
+
+        if paused && !step {
+            continue;
+        }
+
+        // Explore new states or evolve the state space, and evaluate whatever was added
+        let round = agent.train();
+        rounds += 1;
+
+        // A reset agent keeps writing to the same log, carrying on the episode count
+        if let Some(log) = &mut metrics {
+            if let Err(e) = log.record(&agent, &round) {
+                eprintln!("Could not record the round of training, no more will be: {}", e);
+                metrics = None;
+            }
+        }
+
+        let state = agent.get_best_state();
+        if best.as_ref() != Some(&state) {
+            best = Some(state.clone());
+            if reports.send(Report::Best { state, value: agent.max_value }).is_err() {
+                return;
+            }
+        }
+
+        let progress = Progress {
+            round,
+            rounds,
+            best_value: agent.max_value,
+            epsilon: agent.epsilon,
+            states: agent.state_space.len(),
+        };
+
+        // The viewer has gone without stopping training, so there is nobody left to train for
+        if reports.send(Report::Progress(progress)).is_err() {
+            return;
+        }
+    }
+}
+
+// Evaluates the drawing and returns its value, unless the state space is full and it scored lowest of all
+fn submit(agent: &mut Agent, state: &BitVec) -> Option<f32> {
+    agent.add_state(state.clone());
+    agent.update();
+
+    let value = agent.state_space.get(state).copied();
+    match value {
+        Some(value) => match agent.censuses.get(state) {
+            Some(census) => println!("The drawing has a value of {} and ends up as {}", value, census),
+            None => println!("The drawing has a value of {}", value),
+        },
+        None => println!("The drawing scored too low to be kept"),
+    }
+
+    value
+}
+
+// Prints and keeps the settings the run actually uses, including its seed, so it can be repeated
+pub fn save_effective_config(config: &Config) {
+    match config.to_toml() {
+        Ok(text) => println!("Running with the config:\n{}", text),
+        Err(e) => eprintln!("Could not write out the config: {}", e),
+    }
+    if let Err(e) = config.save(&config.files.effective_config) {
+        eprintln!("Could not save the config to {}: {}", config.files.effective_config, e);
+    }
+}
+
+fn save_best_seed(agent: &Agent) {
+    let mut pattern = Pattern::from_state(&agent.max_state, agent.columns, agent.rows);
+    pattern.rule = Some(agent.rule);
+    pattern.comments.push(format!("Best seed found by the agent, with a value of {}", agent.max_value));
+    if let Some(census) = agent.censuses.get(&agent.max_state) {
+        pattern.comments.push(format!("Ends up as {}", census));
+    }
+
+    let path = &agent.config.files.best_seed;
+    match pattern::write_file(path, &pattern) {
+        Ok(()) => println!("Saved the best seed to {}", path),
+        Err(e) => eprintln!("Could not save the best seed to {}: {}", path, e),
+    }
+}
+
+fn save_checkpoint(agent: &Agent) {
+    let path = &agent.config.files.checkpoint;
+    match Checkpoint::new(agent).save(path) {
+        Ok(()) => println!("Saved a checkpoint to {}", path),
+        Err(e) => eprintln!("Could not save a checkpoint to {}: {}", path, e),
+    }
+}